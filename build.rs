@@ -0,0 +1,182 @@
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+const MIN_PRECISION: u8 = 4;
+const POLYNOMIAL_DEGREE: usize = 8;
+
+fn parse_table(path: &str) -> Vec<(u8, String)> {
+    println!("cargo:rerun-if-changed={}", path);
+    let data = fs::read_to_string(path).expect("Unable to read the bias data");
+    data.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (p, values) = line.split_once(':').expect("Malformed bias data");
+            (
+                p.trim().parse().expect("Invalid precision"),
+                values.trim().to_string(),
+            )
+        })
+        .collect()
+}
+
+// `HYPERLOGLOG_BIAS_PRECISIONS` restricts the embedded bias tables to a
+// comma-separated list of precisions or ranges, e.g. `10,12..=14`.
+fn selected_precisions() -> Option<Vec<u8>> {
+    println!("cargo:rerun-if-env-changed=HYPERLOGLOG_BIAS_PRECISIONS");
+    let spec = env::var("HYPERLOGLOG_BIAS_PRECISIONS").ok()?;
+    let mut precisions = vec![];
+    for part in spec
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        if let Some((from, to)) = part.split_once("..=") {
+            let from: u8 = from.trim().parse().expect("Invalid precision range");
+            let to: u8 = to.trim().parse().expect("Invalid precision range");
+            precisions.extend(from..=to);
+        } else {
+            precisions.push(part.parse().expect("Invalid precision"));
+        }
+    }
+    Some(precisions)
+}
+
+// With the `f32_bias_tables` feature, values are rounded to the nearest
+// `f32`, and written in their shortest form.
+fn single_precision(values: &str) -> String {
+    values
+        .split(',')
+        .map(|value| {
+            let value: f64 = value.trim().parse().expect("Invalid bias data");
+            format!("{:?}", value as f32)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn write_tables(
+    out: &mut String,
+    name: &str,
+    table: &[(u8, String)],
+    include: &dyn Fn(u8) -> bool,
+    f32_tables: bool,
+) {
+    writeln!(out, "static {}: &[&[BiasValue]] = &[", name).unwrap();
+    for (p, values) in table {
+        if include(*p) && f32_tables {
+            writeln!(out, "    &[{}],", single_precision(values)).unwrap();
+        } else if include(*p) {
+            writeln!(out, "    &[{}],", values).unwrap();
+        } else {
+            writeln!(out, "    &[],").unwrap();
+        }
+    }
+    writeln!(out, "];").unwrap();
+}
+
+fn parse_values(values: &str) -> Vec<f64> {
+    values
+        .split(',')
+        .map(|value| value.trim().parse().expect("Invalid bias data"))
+        .collect()
+}
+
+// Fit the bias as a polynomial of the raw estimate, both relative to the
+// number of registers, by least squares. The raw estimates are mapped to
+// [-1, 1] to keep the normal equations well conditioned. Return the range of
+// relative raw estimates, followed by the coefficients, lowest degree first.
+fn fit_polynomial(p: u8, raw_estimates: &str, biases: &str) -> Vec<f64> {
+    let m = (1u64 << p) as f64;
+    let xs: Vec<f64> = parse_values(raw_estimates).iter().map(|e| e / m).collect();
+    let ys: Vec<f64> = parse_values(biases).iter().map(|b| b / m).collect();
+    let lo = xs.iter().copied().fold(f64::INFINITY, f64::min);
+    let hi = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let n = POLYNOMIAL_DEGREE + 1;
+    let mut rows = vec![vec![0.0; n + 1]; n];
+    for (&x, &y) in xs.iter().zip(&ys) {
+        let u = (2.0 * x - lo - hi) / (hi - lo);
+        let powers: Vec<f64> = (0..n).map(|i| u.powi(i as i32)).collect();
+        for (row, &pi) in rows.iter_mut().zip(&powers) {
+            for (a, &pj) in row.iter_mut().zip(&powers) {
+                *a += pi * pj;
+            }
+            row[n] += pi * y;
+        }
+    }
+    // Gauss-Jordan elimination with partial pivoting.
+    for c in 0..n {
+        let pivot = (c..n)
+            .max_by(|&r, &s| rows[r][c].abs().total_cmp(&rows[s][c].abs()))
+            .unwrap();
+        rows.swap(c, pivot);
+        let pivot_row = rows[c].clone();
+        for (r, row) in rows.iter_mut().enumerate() {
+            if r != c {
+                let f = row[c] / pivot_row[c];
+                for (a, &b) in row.iter_mut().zip(&pivot_row) {
+                    *a -= f * b;
+                }
+            }
+        }
+    }
+    let mut polynomial = vec![lo, hi];
+    polynomial.extend(rows.iter().enumerate().map(|(i, row)| row[n] / row[i]));
+    polynomial
+}
+
+fn main() {
+    let thresholds = parse_table("data/threshold.txt");
+    let raw_estimates = parse_table("data/raw_estimate.txt");
+    let biases = parse_table("data/bias.txt");
+    assert_eq!(raw_estimates.len(), biases.len());
+    assert!(raw_estimates.iter().zip(&biases).all(|(a, b)| a.0 == b.0));
+    assert_eq!(raw_estimates[0].0, MIN_PRECISION);
+
+    let with_bias_tables = env::var_os("CARGO_FEATURE_WITH_BIAS_TABLES").is_some();
+    let f32_tables = env::var_os("CARGO_FEATURE_F32_BIAS_TABLES").is_some();
+    let polynomial_bias = env::var_os("CARGO_FEATURE_POLYNOMIAL_BIAS").is_some();
+    let selected = selected_precisions();
+    let is_selected = |p: u8| {
+        selected
+            .as_ref()
+            .is_none_or(|selected| selected.contains(&p))
+    };
+    let include = |p: u8| with_bias_tables && is_selected(p);
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "static THRESHOLD_DATA: [f64; {}] = [",
+        thresholds.len()
+    )
+    .unwrap();
+    for (_, value) in &thresholds {
+        writeln!(out, "    {},", value).unwrap();
+    }
+    writeln!(out, "];").unwrap();
+    if polynomial_bias {
+        writeln!(out, "static BIAS_POLYNOMIALS: &[&[f64]] = &[").unwrap();
+        for ((p, raw), (_, bias)) in raw_estimates.iter().zip(&biases) {
+            if is_selected(*p) {
+                writeln!(out, "    &{:?},", fit_polynomial(*p, raw, bias)).unwrap();
+            } else {
+                writeln!(out, "    &[],").unwrap();
+            }
+        }
+        writeln!(out, "];").unwrap();
+    } else {
+        write_tables(
+            &mut out,
+            "RAW_ESTIMATE_DATA",
+            &raw_estimates,
+            &include,
+            f32_tables,
+        );
+        write_tables(&mut out, "BIAS_DATA", &biases, &include, f32_tables);
+    }
+
+    let dest = Path::new(&env::var_os("OUT_DIR").unwrap()).join("weights.rs");
+    fs::write(dest, out).expect("Unable to write the bias tables");
+}
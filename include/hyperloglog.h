@@ -0,0 +1,60 @@
+#ifndef HYPERLOGLOG_H
+#define HYPERLOGLOG_H
+
+/*
+ * C interface to the hyperloglog crate, built with the `ffi` feature as a
+ * dynamic library:
+ *
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ *
+ * Counters are opaque pointers created by hll_new() or hll_deserialize(),
+ * and must be released with hll_free(). Functions returning an int return
+ * 0 on success and -1 on error.
+ */
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct HyperLogLog HyperLogLog;
+
+/* Create a counter with the given error rate, hashing values with
+ * SipHash-1-3 keyed with a 128-bit seed. Return NULL if the error rate is
+ * out of range. */
+HyperLogLog *hll_new(double error_rate, uint64_t seed_lo, uint64_t seed_hi);
+
+/* Release a counter. Does nothing if hll is NULL. */
+void hll_free(HyperLogLog *hll);
+
+/* Insert len bytes. Return 1 if a register was updated, 0 otherwise. */
+int hll_insert(HyperLogLog *hll, const uint8_t *data, size_t len);
+
+/* Insert a 64-bit hash. Return 1 if a register was updated, 0 otherwise. */
+int hll_insert_hash(HyperLogLog *hll, uint64_t hash);
+
+/* Merge src into dst. Fail if the counters are not compatible. */
+int hll_merge(HyperLogLog *dst, const HyperLogLog *src);
+
+/* Return the estimated number of distinct values. */
+double hll_estimate(const HyperLogLog *hll);
+
+/* Wipe the counter. */
+void hll_clear(HyperLogLog *hll);
+
+/* Return the size of the serialized counter, in bytes. */
+size_t hll_serialized_size(const HyperLogLog *hll);
+
+/* Serialize the counter into out. Fail if out_len is too small. */
+int hll_serialize(const HyperLogLog *hll, uint8_t *out, size_t out_len);
+
+/* Create a counter from serialized data. Return NULL if it is invalid. */
+HyperLogLog *hll_deserialize(const uint8_t *data, size_t len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* HYPERLOGLOG_H */
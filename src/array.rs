@@ -0,0 +1,196 @@
+//! Fixed-size arrays of `HyperLogLog` counters stored in a single
+//! allocation.
+
+use std::hash::{Hash, Hasher};
+
+use crate::{Error, HashConfig, HyperLogLog};
+
+/// An array of `N` `HyperLogLog` counters sharing the same parameters, such
+/// as one counter per hour of the day.
+///
+/// The registers of all the counters are stored contiguously in a single
+/// allocation, and the array only stores the parameters once. Counters are
+/// addressed by their index, and panic if it is not lower than `N`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HyperLogLogArray<const N: usize> {
+    p: u8,
+    hash: HashConfig,
+    registers: Vec<u8>,
+}
+
+#[cfg(feature = "zeroize")]
+impl<const N: usize> Drop for HyperLogLogArray<N> {
+    fn drop(&mut self) {
+        self.hash.zeroize();
+    }
+}
+
+impl<const N: usize> HyperLogLogArray<N> {
+    /// Create a new array of counters with the given error rate and seed.
+    #[must_use]
+    pub fn new_deterministic(error_rate: f64, seed: u128) -> Self {
+        Self::with_template(&HyperLogLog::new_deterministic(error_rate, seed))
+    }
+
+    /// Create a new array of counters with the given error rate and a
+    /// random seed. Requires the `random` feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(error_rate: f64) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(error_rate, seed)
+    }
+
+    /// Create a new array of empty counters with the same parameters as
+    /// `template`.
+    #[must_use]
+    pub fn with_template(template: &HyperLogLog) -> Self {
+        HyperLogLogArray {
+            p: template.p,
+            hash: template.hash,
+            registers: vec![0; N << template.p],
+        }
+    }
+
+    /// Insert a new value into the counter at `index`.
+    ///
+    /// Return `true` if a register was updated.
+    pub fn insert<V: Hash>(&mut self, index: usize, value: &V) -> bool {
+        let mut sip = crate::hash::new_hasher(self.hash);
+        value.hash(&mut sip);
+        self.insert_by_hash_value(index, sip.finish())
+    }
+
+    /// Insert a new u64 value into the counter at `index`.
+    ///
+    /// Return `true` if a register was updated.
+    pub fn insert_by_hash_value(&mut self, index: usize, x: u64) -> bool {
+        let (j, rho) = HyperLogLog::register_update(x, self.p);
+        let mjr = &mut self.registers_mut(index)[j];
+        if rho > *mjr {
+            *mjr = rho;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Return the cardinality of the counter at `index`.
+    #[must_use]
+    pub fn len(&self, index: usize) -> f64 {
+        let registers = self.registers(index);
+        HyperLogLog::estimate_with(
+            self.p,
+            HyperLogLog::get_alpha(self.p),
+            HyperLogLog::vec_count_zero(registers),
+            || HyperLogLog::harmonic_sum(registers),
+        )
+    }
+
+    /// Return `true` if the counter at `index` is empty.
+    #[must_use]
+    pub fn is_empty(&self, index: usize) -> bool {
+        self.registers(index).iter().all(|&x| x == 0)
+    }
+
+    /// Return a copy of the counter at `index`.
+    #[must_use]
+    pub fn get(&self, index: usize) -> HyperLogLog {
+        HyperLogLog::from_parts(self.p, self.hash, self.registers(index).to_vec())
+    }
+
+    /// Merge a `HyperLogLog` counter into the counter at `index`.
+    ///
+    /// Return an error if the counter doesn't have the same parameters and
+    /// seed as the array.
+    pub fn merge_at(&mut self, index: usize, src: &HyperLogLog) -> Result<(), Error> {
+        if src.p != self.p {
+            return Err(Error::IncompatiblePrecision);
+        }
+        self.hash.check_compatible(src.hash)?;
+        for (mir, &src_mir) in self.registers_mut(index).iter_mut().zip(src.M.iter()) {
+            *mir = (*mir).max(src_mir);
+        }
+        Ok(())
+    }
+
+    /// Merge another array into the current one, index by index.
+    ///
+    /// Return an error if the arrays don't have the same parameters and
+    /// seed.
+    pub fn merge(&mut self, src: &HyperLogLogArray<N>) -> Result<(), Error> {
+        if src.p != self.p {
+            return Err(Error::IncompatiblePrecision);
+        }
+        self.hash.check_compatible(src.hash)?;
+        for (mir, &src_mir) in self.registers.iter_mut().zip(&src.registers) {
+            *mir = (*mir).max(src_mir);
+        }
+        Ok(())
+    }
+
+    /// Return a counter of all the values inserted into the array,
+    /// regardless of their index.
+    #[must_use]
+    pub fn union(&self) -> HyperLogLog {
+        let m = 1usize << self.p;
+        let mut M = vec![0u8; m];
+        for registers in self.registers.chunks_exact(m) {
+            for (mir, &x) in M.iter_mut().zip(registers) {
+                *mir = (*mir).max(x);
+            }
+        }
+        HyperLogLog::from_parts(self.p, self.hash, M)
+    }
+
+    /// Wipe all the counters.
+    pub fn clear(&mut self) {
+        self.registers.iter_mut().for_each(|x| *x = 0);
+    }
+
+    fn registers(&self, index: usize) -> &[u8] {
+        assert!(index < N);
+        let m = 1usize << self.p;
+        &self.registers[index * m..(index + 1) * m]
+    }
+
+    fn registers_mut(&mut self, index: usize) -> &mut [u8] {
+        assert!(index < N);
+        let m = 1usize << self.p;
+        &mut self.registers[index * m..(index + 1) * m]
+    }
+}
+
+#[cfg(any(feature = "with_bias_tables", feature = "polynomial_bias"))]
+#[test]
+fn array_test_counters() {
+    let mut array = HyperLogLogArray::<24>::new_deterministic(0.001, 1);
+    assert!(array.is_empty(0));
+    for x in 0..24_000u32 {
+        array.insert((x % 24) as usize, &x);
+    }
+    for index in 0..24 {
+        assert!((array.len(index) - 1_000.0).abs() < 50.0);
+        assert!((array.get(index).len() - array.len(index)).abs() < f64::EPSILON);
+    }
+    assert!((array.union().len() - 24_000.0).abs() < 1_200.0);
+
+    let mut other = HyperLogLogArray::with_template(&array.get(0));
+    for x in 0..1_000u32 {
+        other.insert(0, &(x + 1_000_000));
+    }
+    array.merge(&other).unwrap();
+    assert!((array.len(0) - 2_000.0).abs() < 100.0);
+    let hll = array.get(0);
+    array.merge_at(1, &hll).unwrap();
+    assert!((array.len(1) - 3_000.0).abs() < 150.0);
+
+    let incompatible = HyperLogLog::new_deterministic(0.001, 2);
+    assert_eq!(
+        array.merge_at(0, &incompatible),
+        Err(Error::IncompatibleSeed)
+    );
+    array.clear();
+    assert!((0..24).all(|index| array.is_empty(index)));
+}
@@ -0,0 +1,147 @@
+//! A `HyperLogLog` counter that can be updated concurrently without locks.
+
+use std::hash::Hash;
+use std::sync::atomic::AtomicU8;
+
+use crate::{Error, HashConfig, HyperLogLog};
+
+/// A HyperLogLog counter whose registers are atomics, so that many threads
+/// can insert values into a shared counter without locking.
+///
+/// This is a `HyperLogLog` counter using the `Vec<AtomicU8>` [register
+/// store](crate::RegisterStore), whose methods take `&self`.
+///
+/// Registers only ever increase, so concurrent estimates are always
+/// consistent with a state that the counter went through or will go
+/// through.
+#[derive(Debug)]
+pub struct AtomicHyperLogLog(HyperLogLog<Vec<AtomicU8>>);
+
+impl AtomicHyperLogLog {
+    /// Create a new `AtomicHyperLogLog` counter with the given error rate and
+    /// seed.
+    #[must_use]
+    pub fn new_deterministic(error_rate: f64, seed: u128) -> Self {
+        let p = HyperLogLog::precision_for_error_rate(error_rate);
+        Self::from_parts(HashConfig::with_seed(seed), vec![0; 1usize << p])
+    }
+
+    /// Create a new `AtomicHyperLogLog` counter with the given error rate and
+    /// a random seed. Requires the `random` feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(error_rate: f64) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(error_rate, seed)
+    }
+
+    /// Create a new, empty `AtomicHyperLogLog` counter with the same
+    /// parameters as a `HyperLogLog` counter, so that both can be merged.
+    #[must_use]
+    pub fn new_from_template(hll: &HyperLogLog) -> Self {
+        Self::from_parts(hll.hash, vec![0; hll.m])
+    }
+
+    fn from_parts(hash: HashConfig, M: Vec<u8>) -> Self {
+        let M = M.into_iter().map(AtomicU8::new).collect();
+        AtomicHyperLogLog(HyperLogLog::with_store(M, hash).expect("Valid registers"))
+    }
+
+    /// Insert a new value into the counter.
+    ///
+    /// Return `true` if a register was updated by this call.
+    pub fn insert<V: Hash>(&self, value: &V) -> bool {
+        self.0.insert_shared(value)
+    }
+
+    /// Insert a new u64 value into the counter.
+    ///
+    /// Return `true` if a register was updated by this call.
+    pub fn insert_by_hash_value(&self, x: u64) -> bool {
+        self.0.insert_by_hash_value_shared(x)
+    }
+
+    /// Return the cardinality of the counter.
+    ///
+    /// This can be called while other threads are inserting values.
+    #[must_use]
+    pub fn len(&self) -> f64 {
+        self.0.len()
+    }
+
+    /// Return `true` if the counter is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Merge a `HyperLogLog` counter into the current one.
+    ///
+    /// Return an error if the counters don't have the same parameters and
+    /// hash function.
+    pub fn merge(&self, src: &HyperLogLog) -> Result<(), Error> {
+        self.0.merge_shared(src)
+    }
+
+    /// Return a regular `HyperLogLog` counter with a copy of the current
+    /// registers.
+    #[must_use]
+    pub fn to_hyperloglog(&self) -> HyperLogLog {
+        self.0.to_hyperloglog()
+    }
+
+    /// Wipe the counter.
+    pub fn clear(&self) {
+        self.0.clear_shared();
+    }
+}
+
+impl From<HyperLogLog> for AtomicHyperLogLog {
+    fn from(hll: HyperLogLog) -> Self {
+        Self::from_parts(hll.hash, hll.M.to_vec())
+    }
+}
+
+#[cfg(any(feature = "with_bias_tables", feature = "polynomial_bias"))]
+#[test]
+fn atomic_test_concurrent_inserts() {
+    let hll = AtomicHyperLogLog::new_deterministic(0.001, 1);
+    assert!(hll.is_empty());
+    std::thread::scope(|s| {
+        for t in 0..4u32 {
+            let hll = &hll;
+            s.spawn(move || {
+                for i in 0..10_000u32 {
+                    hll.insert(&(t * 5_000 + i));
+                }
+            });
+        }
+    });
+    assert!((hll.len() - 25_000.0).abs() < 1_000.0);
+
+    let mut expected = HyperLogLog::new_with_hash_config(0.001, hll.0.hash);
+    expected.insert_all(0..25_000u32);
+    assert_eq!(hll.to_hyperloglog().registers(), expected.registers());
+    assert!((hll.len() - expected.len()).abs() < f64::EPSILON);
+}
+
+#[test]
+fn atomic_test_merge() {
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1);
+    hll.insert_all(0..100u32);
+    let atomic = AtomicHyperLogLog::new_from_template(&hll);
+    assert!(atomic.insert(&1000u32));
+    assert!(!atomic.insert(&1000u32));
+    atomic.merge(&hll).unwrap();
+    hll.insert(&1000u32);
+    assert_eq!(atomic.to_hyperloglog().registers(), hll.registers());
+    let atomic = AtomicHyperLogLog::from(hll.clone());
+    assert_eq!(atomic.to_hyperloglog().registers(), hll.registers());
+    atomic.clear();
+    assert!(atomic.is_empty());
+
+    let other = HyperLogLog::new_deterministic(0.001, 2);
+    assert_eq!(atomic.merge(&other), Err(Error::IncompatibleSeed));
+    let other = HyperLogLog::new_deterministic(0.01, 1);
+    assert_eq!(atomic.merge(&other), Err(Error::IncompatiblePrecision));
+}
@@ -0,0 +1,212 @@
+//! Approximate distinct counts from the command line.
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead};
+use std::process;
+
+use hyperloglog::ops::intersection_many;
+use hyperloglog::{HyperLogLog, HyperLogLogBuilder};
+
+const USAGE: &str = "\
+Usage: hll [options] < input
+       hll merge <file>... -o <output>
+       hll compare <file> <file>
+
+Print the approximate number of distinct lines read from stdin, merge
+counters saved with --output into a single one, or compare the sets
+represented by two counters, with 95% confidence intervals.
+
+Options:
+    -e, --error-rate RATE   error rate of the counter (default: 0.01)
+    -s, --seed SEED         seed of the hash function (default: 0)
+    -0, --null              records are terminated by NUL instead of newlines
+    -o, --output FILE       save the counter to FILE
+    -h, --help              print this help";
+
+enum Command {
+    Count {
+        builder: HyperLogLogBuilder,
+        delimiter: u8,
+        output: Option<String>,
+    },
+    Merge {
+        inputs: Vec<String>,
+        output: String,
+    },
+    Compare {
+        a: String,
+        b: String,
+    },
+}
+
+fn parse_command(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut builder = HyperLogLogBuilder::new().error_rate(0.01).seed(0);
+    let (mut delimiter, mut output, mut inputs) = (b'\n', None, vec![]);
+    let mut args = args.peekable();
+    let subcommand = args.next_if(|arg| arg == "merge" || arg == "compare");
+    let takes_files = subcommand.is_some();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("Missing value for {}", name));
+        match arg.as_str() {
+            "-e" | "--error-rate" if !takes_files => {
+                let rate = value(&arg)?;
+                let rate = rate
+                    .parse()
+                    .map_err(|_| format!("Invalid error rate: {}", rate))?;
+                builder = builder.error_rate(rate);
+            }
+            "-s" | "--seed" if !takes_files => {
+                let seed = value(&arg)?;
+                let seed = seed
+                    .parse()
+                    .map_err(|_| format!("Invalid seed: {}", seed))?;
+                builder = builder.seed(seed);
+            }
+            "-0" | "--null" if !takes_files => delimiter = 0,
+            "-o" | "--output" => output = Some(value(&arg)?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if takes_files && !arg.starts_with('-') => inputs.push(arg),
+            _ => return Err(format!("Unexpected argument: {}", arg)),
+        }
+    }
+    if subcommand.as_deref() == Some("compare") {
+        if inputs.len() != 2 || output.is_some() {
+            return Err("Expected two counters to compare".to_string());
+        }
+        let b = inputs.pop().unwrap();
+        let a = inputs.pop().unwrap();
+        return Ok(Command::Compare { a, b });
+    }
+    if takes_files {
+        if inputs.is_empty() {
+            return Err("No counters to merge".to_string());
+        }
+        let output = output.ok_or("Missing output file")?;
+        return Ok(Command::Merge { inputs, output });
+    }
+    builder.build().map_err(|e| e.to_string())?;
+    Ok(Command::Count {
+        builder,
+        delimiter,
+        output,
+    })
+}
+
+fn count(builder: &HyperLogLogBuilder, delimiter: u8) -> io::Result<HyperLogLog> {
+    let mut hll = builder.build().expect("Options are validated");
+    let mut input = io::stdin().lock();
+    let mut record = Vec::new();
+    while input.read_until(delimiter, &mut record)? > 0 {
+        if record.last() == Some(&delimiter) {
+            record.pop();
+        }
+        hll.insert_bytes(&record);
+        record.clear();
+    }
+    Ok(hll)
+}
+
+fn load(path: &str) -> Result<HyperLogLog, String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    HyperLogLog::from_bytes(&bytes).map_err(|e| format!("{}: {}", path, e))
+}
+
+fn merge(inputs: &[String]) -> Result<HyperLogLog, String> {
+    let mut hll = load(&inputs[0])?;
+    for path in &inputs[1..] {
+        hll.try_merge(&load(path)?)
+            .map_err(|e| format!("{}: {}", path, e))?;
+    }
+    Ok(hll)
+}
+
+// Print the estimates of the set operations on `a` and `b`, with 95%
+// confidence intervals derived from the standard errors of the union and
+// individual estimates.
+fn compare(a: &HyperLogLog, b: &HyperLogLog) -> Result<(), String> {
+    let intersection = intersection_many(&[a, b]).map_err(|e| e.to_string())?;
+    let error_rate = a.expected_error();
+    let (len_a, len_b, union) = (a.len(), b.len(), a.union_cardinality(b));
+    let z = 1.96;
+    let (i, i_delta) = (intersection.estimate, z * intersection.std_error);
+    let difference_delta = |len: f64| z * error_rate * (union * union + len * len).sqrt();
+    let ratio = |x: f64, delta: f64, total: f64| {
+        if total == 0.0 {
+            (0.0, 0.0, 0.0)
+        } else {
+            (
+                ((x - delta) / total).clamp(0.0, 1.0),
+                (x / total).clamp(0.0, 1.0),
+                ((x + delta) / total).clamp(0.0, 1.0),
+            )
+        }
+    };
+    let print_len = |name: &str, x: f64, delta: f64| {
+        println!(
+            "{:<16}{:>12.0}  [{:.0}, {:.0}]",
+            name,
+            x,
+            (x - delta).max(0.0),
+            x + delta
+        );
+    };
+    let print_ratio = |name: &str, (low, x, high): (f64, f64, f64)| {
+        println!("{:<16}{:>12.4}  [{:.4}, {:.4}]", name, x, low, high);
+    };
+    print_len("a", len_a, z * error_rate * len_a);
+    print_len("b", len_b, z * error_rate * len_b);
+    print_len("union", union, z * error_rate * union);
+    print_len("intersection", i, i_delta);
+    print_len(
+        "a - b",
+        a.difference_cardinality(b),
+        difference_delta(len_b),
+    );
+    print_len(
+        "b - a",
+        b.difference_cardinality(a),
+        difference_delta(len_a),
+    );
+    print_ratio("jaccard", ratio(i, i_delta, union));
+    print_ratio("a in b", ratio(i, i_delta, len_a));
+    print_ratio("b in a", ratio(i, i_delta, len_b));
+    if !intersection.is_significant() {
+        println!("\nThe intersection is too small to be estimated reliably.");
+    }
+    Ok(())
+}
+
+fn run(command: Command) -> Result<(), String> {
+    let (hll, output) = match command {
+        Command::Count {
+            builder,
+            delimiter,
+            output,
+        } => (
+            count(&builder, delimiter).map_err(|e| e.to_string())?,
+            output,
+        ),
+        Command::Merge { inputs, output } => (merge(&inputs)?, Some(output)),
+        Command::Compare { a, b } => return compare(&load(&a)?, &load(&b)?),
+    };
+    if let Some(path) = output {
+        fs::write(&path, hll.to_bytes()).map_err(|e| format!("{}: {}", path, e))?;
+    }
+    println!("{}", hll.len().round());
+    Ok(())
+}
+
+fn main() {
+    let command = parse_command(env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+    if let Err(e) = run(command) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
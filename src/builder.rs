@@ -0,0 +1,154 @@
+//! A builder to configure new `HyperLogLog` counters.
+
+use crate::{Error, HashConfig, HyperLogLog, DEFAULT_PRECISION, MAX_PRECISION, MIN_PRECISION};
+
+/// A builder for `HyperLogLog` counters, returned by
+/// [`HyperLogLog::builder`].
+///
+/// ```
+/// use hyperloglog::{HashConfig, HyperLogLog};
+///
+/// let hll = HyperLogLog::builder()
+///     .error_rate(0.01)
+///     .hasher(HashConfig::SipHash13 { seed: 42 })
+///     .build()
+///     .unwrap();
+/// assert_eq!(hll.seed(), 42);
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HyperLogLogBuilder {
+    size: Option<Size>,
+    hash: Option<HashConfig>,
+    track_changes: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Size {
+    ErrorRate(f64),
+    Precision(u8),
+}
+
+impl HyperLogLogBuilder {
+    /// Create a new builder. Without further configuration, counters have a
+    /// precision of 14 and a random seed, or a zero seed if the `random`
+    /// feature is disabled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the error rate. It must map to a precision between 4 and 26.
+    /// Replaces any precision previously set with
+    /// [`precision`](Self::precision).
+    #[must_use]
+    pub fn error_rate(mut self, error_rate: f64) -> Self {
+        self.size = Some(Size::ErrorRate(error_rate));
+        self
+    }
+
+    /// Set the precision, between 4 and 26. Replaces any error rate
+    /// previously set with [`error_rate`](Self::error_rate).
+    #[must_use]
+    pub fn precision(mut self, p: u8) -> Self {
+        self.size = Some(Size::Precision(p));
+        self
+    }
+
+    /// Key the default hash function with `seed`. Replaces any hash function
+    /// previously set with [`hasher`](Self::hasher).
+    #[must_use]
+    pub fn seed(mut self, seed: u128) -> Self {
+        self.hash = Some(HashConfig::with_seed(seed));
+        self
+    }
+
+    /// Set the hash function and its seed. Replaces any seed previously set
+    /// with [`seed`](Self::seed).
+    #[must_use]
+    pub fn hasher(mut self, hash: HashConfig) -> Self {
+        self.hash = Some(hash);
+        self
+    }
+
+    /// Track which registers are updated, as with
+    /// [`HyperLogLog::track_changes`].
+    #[must_use]
+    pub fn track_changes(mut self) -> Self {
+        self.track_changes = true;
+        self
+    }
+
+    /// Build the counter.
+    ///
+    /// Return an error if the precision, or the precision the error rate
+    /// maps to, is not supported, or if the hash function is not enabled.
+    pub fn build(&self) -> Result<HyperLogLog, Error> {
+        let p = match self.size {
+            Some(Size::ErrorRate(error_rate)) => {
+                HyperLogLog::try_precision_for_error_rate(error_rate)?
+            }
+            Some(Size::Precision(p)) if (MIN_PRECISION..=MAX_PRECISION).contains(&p) => p,
+            Some(Size::Precision(_)) => return Err(Error::InvalidPrecision),
+            None => DEFAULT_PRECISION,
+        };
+        let hash = match self.hash {
+            Some(hash) => hash,
+            None => HashConfig::with_seed(default_seed()),
+        };
+        hash.check_supported()?;
+        let mut hll = HyperLogLog::from_parts(p, hash, vec![0; 1usize << p]);
+        if self.track_changes {
+            hll.track_changes();
+        }
+        Ok(hll)
+    }
+}
+
+#[cfg(feature = "random")]
+fn default_seed() -> u128 {
+    rand::random()
+}
+
+#[cfg(not(feature = "random"))]
+fn default_seed() -> u128 {
+    0
+}
+
+#[test]
+fn builder_test_build() {
+    let hll = HyperLogLog::builder().build().unwrap();
+    assert_eq!(hll.num_registers(), 1 << DEFAULT_PRECISION);
+
+    let hll = HyperLogLog::builder()
+        .error_rate(0.001)
+        .seed(7)
+        .build()
+        .unwrap();
+    let expected = HyperLogLog::new_deterministic(0.001, 7);
+    assert!(hll.is_compatible(&expected));
+    assert!(!hll.tracks_changes());
+    assert!(HyperLogLog::builder()
+        .track_changes()
+        .build()
+        .unwrap()
+        .tracks_changes());
+
+    let hash = HashConfig::Murmur3 { seed: 7 };
+    let hll = HyperLogLog::builder().seed(1).hasher(hash).build().unwrap();
+    assert_eq!(hll.hash_config(), hash);
+
+    let res = HyperLogLog::builder().error_rate(0.5).build();
+    assert_eq!(res.unwrap_err(), Error::InvalidPrecision);
+    let res = HyperLogLog::builder().error_rate(0.000001).build();
+    assert_eq!(res.unwrap_err(), Error::InvalidPrecision);
+    let res = HyperLogLog::builder().error_rate(f64::NAN).build();
+    assert_eq!(res.unwrap_err(), Error::InvalidPrecision);
+
+    let hll = HyperLogLog::builder()
+        .error_rate(0.04)
+        .precision(10)
+        .build();
+    assert_eq!(hll.unwrap().precision(), 10);
+    let res = HyperLogLog::builder().precision(27).build();
+    assert_eq!(res.unwrap_err(), Error::InvalidPrecision);
+}
@@ -0,0 +1,403 @@
+//! Classic cardinality estimators, for research reproduction and
+//! compatibility with systems storing their state.
+
+use std::hash::{Hash, Hasher};
+
+use crate::{Error, HashConfig, HyperLogLog, MAX_PRECISION, MIN_PRECISION};
+
+/// The correction factor of the Flajolet-Martin estimator.
+const PCSA_PHI: f64 = 0.77351;
+
+/// A Probabilistic Counting with Stochastic Averaging (PCSA) sketch, as
+/// described in "Probabilistic Counting Algorithms for Data Base
+/// Applications", Flajolet and Martin, 1985.
+///
+/// The state is made of `2^p` 64-bit bitmaps. A value sets, in the bitmap
+/// selected by the low bits of its hash, the bit whose index is the
+/// position of the first set bit of the rest of the hash, starting at 0.
+/// The estimate is `m/phi * 2^(R/m)`, where `R` is the sum over all bitmaps
+/// of the index of their lowest unset bit. Its relative standard error is
+/// about `0.78/sqrt(m)`.
+///
+/// The original estimator is used without any correction, and
+/// overestimates cardinalities below about `10*m`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Pcsa {
+    p: u8,
+    hash: HashConfig,
+    bitmaps: Vec<u64>,
+}
+
+crate::hash::zeroize_on_drop!(Pcsa);
+
+impl Pcsa {
+    /// Create a new PCSA sketch with `2^p` bitmaps and the given seed.
+    ///
+    /// Panics if `p` is not between 4 and 26.
+    #[must_use]
+    pub fn new_deterministic(p: u8, seed: u128) -> Self {
+        assert!((MIN_PRECISION..=MAX_PRECISION).contains(&p));
+        Pcsa {
+            p,
+            hash: HashConfig::with_seed(seed),
+            bitmaps: vec![0; 1usize << p],
+        }
+    }
+
+    /// Create a new PCSA sketch with `2^p` bitmaps and a random seed.
+    /// Requires the `random` feature.
+    ///
+    /// Panics if `p` is not between 4 and 26.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(p: u8) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(p, seed)
+    }
+
+    /// Create a PCSA sketch from existing bitmaps, whose number must be a
+    /// power of 2 between 16 and `2^26`.
+    pub fn from_bitmaps(bitmaps: Vec<u64>, hash: HashConfig) -> Result<Self, Error> {
+        if !bitmaps.len().is_power_of_two() {
+            return Err(Error::InvalidRegisterCount);
+        }
+        let p = bitmaps.len().trailing_zeros() as u8;
+        if !(MIN_PRECISION..=MAX_PRECISION).contains(&p) {
+            return Err(Error::InvalidPrecision);
+        }
+        Ok(Pcsa { p, hash, bitmaps })
+    }
+
+    /// Return the bitmaps.
+    #[must_use]
+    pub fn bitmaps(&self) -> &[u64] {
+        &self.bitmaps
+    }
+
+    /// Insert a new value into the sketch.
+    pub fn insert<V: Hash>(&mut self, value: &V) {
+        let mut sip = crate::hash::new_hasher(self.hash);
+        value.hash(&mut sip);
+        self.insert_by_hash_value(sip.finish());
+    }
+
+    /// Insert a new u64 value into the sketch.
+    pub fn insert_by_hash_value(&mut self, x: u64) {
+        let (j, rho) = HyperLogLog::register_update(x, self.p);
+        self.bitmaps[j] |= 1 << (rho - 1).min(63);
+    }
+
+    /// Return the cardinality of the sketch.
+    #[must_use]
+    pub fn len(&self) -> f64 {
+        let m = self.bitmaps.len() as f64;
+        let r: u32 = self.bitmaps.iter().map(|&b| b.trailing_ones()).sum();
+        m / PCSA_PHI * 2.0f64.powf(r as f64 / m)
+    }
+
+    /// Return `true` if the sketch is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.bitmaps.iter().all(|&b| b == 0)
+    }
+
+    /// Merge another sketch into the current one.
+    ///
+    /// Return an error if the sketches don't have the same number of
+    /// bitmaps and hash function.
+    pub fn merge(&mut self, src: &Pcsa) -> Result<(), Error> {
+        if src.p != self.p {
+            return Err(Error::IncompatiblePrecision);
+        }
+        self.hash.check_compatible(src.hash)?;
+        for (b, &src_b) in self.bitmaps.iter_mut().zip(&src.bitmaps) {
+            *b |= src_b;
+        }
+        Ok(())
+    }
+
+    /// Wipe the sketch.
+    pub fn clear(&mut self) {
+        self.bitmaps.iter_mut().for_each(|b| *b = 0);
+    }
+}
+
+/// The largest precision of [`LegacyHyperLogLog`] counters, leaving at
+/// least 16 bits of the hash to compute register values.
+const LEGACY_MAX_PRECISION: u8 = 16;
+
+/// A `HyperLogLog` counter using 32-bit hashes, as in the original paper,
+/// "HyperLogLog: the analysis of a near-optimal cardinality estimation
+/// algorithm", Flajolet et al., 2007, and in older deployments such as
+/// stream-lib.
+///
+/// A value updates the register selected by the top `p` bits of its hash,
+/// with the position of the first set bit of the remaining bits. The raw
+/// estimate is corrected with linear counting below `5m/2`, and with the
+/// large range correction `-2^32 * ln(1 - E/2^32)` above `2^32/30`, since
+/// hash collisions become frequent. Registers of such counters can be
+/// loaded with [`from_registers`](Self::from_registers) to continue them.
+///
+/// Use [`HyperLogLog`] for new counters: the estimate saturates as the
+/// cardinality approaches `2^32`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LegacyHyperLogLog {
+    p: u8,
+    hash: HashConfig,
+    M: Vec<u8>,
+}
+
+crate::hash::zeroize_on_drop!(LegacyHyperLogLog);
+
+impl LegacyHyperLogLog {
+    /// Create a new counter with `2^p` registers, hashing values with
+    /// MurmurHash3_x86_32 and the given seed.
+    ///
+    /// Panics if `p` is not between 4 and 16.
+    #[must_use]
+    pub fn new_deterministic(p: u8, seed: u32) -> Self {
+        assert!((MIN_PRECISION..=LEGACY_MAX_PRECISION).contains(&p));
+        LegacyHyperLogLog {
+            p,
+            hash: HashConfig::Murmur3 { seed },
+            M: vec![0; 1usize << p],
+        }
+    }
+
+    /// Create a counter from the registers of an existing one, whose number
+    /// must be a power of 2 between 16 and `2^16`.
+    ///
+    /// Values inserted afterwards are only correctly deduplicated with the
+    /// existing ones if they are inserted with
+    /// [`insert_by_hash_value`](Self::insert_by_hash_value) and the
+    /// original hash function, or if `hash` matches it.
+    pub fn from_registers(registers: Vec<u8>, hash: HashConfig) -> Result<Self, Error> {
+        if !registers.len().is_power_of_two() {
+            return Err(Error::InvalidRegisterCount);
+        }
+        let p = registers.len().trailing_zeros() as u8;
+        if !(MIN_PRECISION..=LEGACY_MAX_PRECISION).contains(&p) {
+            return Err(Error::InvalidPrecision);
+        }
+        if registers.iter().any(|&x| x > 32 - p + 1) {
+            return Err(Error::InvalidRegisterValue);
+        }
+        Ok(LegacyHyperLogLog {
+            p,
+            hash,
+            M: registers,
+        })
+    }
+
+    /// Return the content of the registers.
+    #[must_use]
+    pub fn registers(&self) -> &[u8] {
+        &self.M
+    }
+
+    /// Insert a new value into the counter, using its MurmurHash3_x86_32
+    /// hash, as stream-lib does. The hash is seeded with the low 32 bits of
+    /// the seed of the hash configuration.
+    pub fn insert<V: Hash>(&mut self, value: &V) {
+        let mut hasher = crate::hash::new_hasher32(self.hash.seed() as u32);
+        value.hash(&mut hasher);
+        self.insert_by_hash_value(hasher.finish() as u32);
+    }
+
+    /// Insert a new 32-bit hash into the counter.
+    pub fn insert_by_hash_value(&mut self, x: u32) {
+        let j = (x >> (32 - self.p)) as usize;
+        let rho = ((x << self.p) | 1 << (self.p - 1)).leading_zeros() as u8 + 1;
+        self.M[j] = self.M[j].max(rho);
+    }
+
+    /// Return the cardinality of the counter.
+    #[must_use]
+    pub fn len(&self) -> f64 {
+        let m = self.M.len() as f64;
+        let V = HyperLogLog::vec_count_zero(&self.M);
+        let sum = HyperLogLog::harmonic_sum(&self.M);
+        let estimate = HyperLogLog::get_alpha(self.p) * m * m / sum;
+        let two_32 = 2.0f64.powi(32);
+        if estimate <= 2.5 * m && V > 0 {
+            m * (m / V as f64).ln()
+        } else if estimate > two_32 / 30.0 {
+            -two_32 * (1.0 - estimate / two_32).max(f64::MIN_POSITIVE).ln()
+        } else {
+            estimate
+        }
+    }
+
+    /// Return `true` if the counter is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.M.iter().all(|&x| x == 0)
+    }
+
+    /// Merge another counter into the current one.
+    ///
+    /// Return an error if the counters don't have the same number of
+    /// registers and hash function.
+    pub fn merge(&mut self, src: &LegacyHyperLogLog) -> Result<(), Error> {
+        if src.p != self.p {
+            return Err(Error::IncompatiblePrecision);
+        }
+        self.hash.check_compatible(src.hash)?;
+        for (mir, &src_mir) in self.M.iter_mut().zip(src.M.iter()) {
+            *mir = (*mir).max(src_mir);
+        }
+        Ok(())
+    }
+
+    /// Wipe the counter.
+    pub fn clear(&mut self) {
+        self.M.iter_mut().for_each(|x| *x = 0);
+    }
+}
+
+/// An estimator computing a cardinality from `HyperLogLog` registers, for
+/// [`HyperLogLog::len_with`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Estimator {
+    /// The `HyperLogLog` estimator with bias correction, as used by
+    /// [`HyperLogLog::len`].
+    HyperLogLog,
+    /// The LogLog estimator, "Loglog Counting of Large Cardinalities",
+    /// Durand and Flajolet, 2003: `alpha * m * 2^(mean of the registers)`.
+    /// Its relative standard error is about `1.30/sqrt(m)`, and it
+    /// overestimates cardinalities below about `10*m`.
+    LogLog,
+    /// The SuperLogLog estimator from the same paper, only averaging the
+    /// smallest 70% of the registers to reduce the impact of outliers. Its
+    /// relative standard error is about `1.05/sqrt(m)`.
+    SuperLogLog,
+}
+
+// Asymptotic LogLog bias correction constant.
+const LOGLOG_ALPHA: f64 = 0.39701;
+
+// SuperLogLog bias correction constant for the 70% truncation rule,
+// calibrated with `m` registers rather than the number of retained ones.
+const SUPERLOGLOG_ALPHA: f64 = 0.7686;
+
+impl HyperLogLog {
+    /// Return the cardinality of the counter computed by the given
+    /// estimator, from the same registers.
+    #[must_use]
+    pub fn len_with(&self, estimator: Estimator) -> f64 {
+        let m = self.m as f64;
+        match estimator {
+            Estimator::HyperLogLog => self.len(),
+            Estimator::LogLog => {
+                let alpha = LOGLOG_ALPHA
+                    - (2.0 * std::f64::consts::PI.powi(2) + std::f64::consts::LN_2.powi(2))
+                        / (48.0 * m);
+                let mean = self.M.iter().map(|&x| x as f64).sum::<f64>() / m;
+                alpha * m * 2.0f64.powf(mean)
+            }
+            Estimator::SuperLogLog => {
+                let m0 = (self.m * 7 / 10).max(1);
+                let hist = Self::register_histogram(&self.M);
+                let (mut sum, mut left) = (0.0, m0);
+                for (x, &count) in hist.iter().enumerate() {
+                    let taken = left.min(count as usize);
+                    sum += (x * taken) as f64;
+                    left -= taken;
+                }
+                SUPERLOGLOG_ALPHA * m * 2.0f64.powf(sum / m0 as f64)
+            }
+        }
+    }
+}
+
+#[test]
+fn classic_test_pcsa() {
+    let mut pcsa = Pcsa::new_deterministic(10, 1);
+    assert!(pcsa.is_empty());
+    for i in 0..1_000_000u32 {
+        pcsa.insert(&i);
+    }
+    assert!((pcsa.len() - 1_000_000.0).abs() < 75_000.0);
+
+    let mut other = Pcsa::new_deterministic(10, 1);
+    for i in 500_000..1_500_000u32 {
+        other.insert(&i);
+    }
+    pcsa.merge(&other).unwrap();
+    assert!((pcsa.len() - 1_500_000.0).abs() < 110_000.0);
+    assert_eq!(
+        pcsa.merge(&Pcsa::new_deterministic(10, 2)),
+        Err(Error::IncompatibleSeed)
+    );
+
+    let copy = Pcsa::from_bitmaps(pcsa.bitmaps().to_vec(), pcsa.hash).unwrap();
+    assert_eq!(copy, pcsa);
+    let res = Pcsa::from_bitmaps(vec![0; 15], pcsa.hash);
+    assert_eq!(res.unwrap_err(), Error::InvalidRegisterCount);
+    let res = Pcsa::from_bitmaps(vec![0; 8], pcsa.hash);
+    assert_eq!(res.unwrap_err(), Error::InvalidPrecision);
+    pcsa.clear();
+    assert!(pcsa.is_empty());
+}
+
+#[test]
+fn classic_test_legacy() {
+    let mut hll = LegacyHyperLogLog::new_deterministic(12, 1);
+    assert!(hll.is_empty() && hll.len() == 0.0);
+    for x in 0..1_000u32 {
+        hll.insert(&x);
+    }
+    assert!((hll.len() - 1_000.0).abs() < 50.0);
+    for x in 0..1_000_000u32 {
+        hll.insert(&x);
+    }
+    assert!((hll.len() - 1_000_000.0).abs() < 50_000.0);
+
+    // Register values from the reference bit layout: index in the top bits,
+    // rank of the remaining bits.
+    let mut hll = LegacyHyperLogLog::new_deterministic(4, 1);
+    hll.insert_by_hash_value(0x3800_0000);
+    hll.insert_by_hash_value(0x0000_0000);
+    assert_eq!(hll.registers()[3], 1);
+    assert_eq!(hll.registers()[0], 29);
+
+    let hash = HashConfig::Murmur3 { seed: 1 };
+    let copy = LegacyHyperLogLog::from_registers(hll.registers().to_vec(), hash);
+    assert_eq!(copy.as_ref(), Ok(&hll));
+    let mut registers = hll.registers().to_vec();
+    registers[0] = 30;
+    assert_eq!(
+        LegacyHyperLogLog::from_registers(registers, hash),
+        Err(Error::InvalidRegisterValue)
+    );
+
+    // Values are hashed with MurmurHash3_x86_32.
+    let mut hll = LegacyHyperLogLog::new_deterministic(12, 7);
+    let mut expected = hll.clone();
+    hll.insert(&"hello");
+    expected.insert_by_hash_value(crate::hash::murmur3_x86_32(b"hello\xff", 7));
+    assert_eq!(hll, expected);
+
+    // Near 2^32 distinct hashes, the large range correction applies.
+    let mut hll = LegacyHyperLogLog::new_deterministic(14, 1);
+    for (j, x) in hll.M.iter_mut().enumerate() {
+        *x = 14 + (j % 3) as u8;
+    }
+    let raw = HyperLogLog::get_alpha(14) * 2.0f64.powi(28) / HyperLogLog::harmonic_sum(&hll.M);
+    assert!(raw > 2.0f64.powi(32) / 30.0 && hll.len() > raw);
+}
+
+#[test]
+fn classic_test_estimators() {
+    let mut hll = HyperLogLog::new_deterministic(HyperLogLog::error_rate_for_precision(12), 1);
+    hll.insert_all(0..1_000_000u32);
+    let estimate = hll.len_with(Estimator::HyperLogLog);
+    assert!((estimate - hll.len()).abs() < f64::EPSILON);
+    for &estimator in &[Estimator::LogLog, Estimator::SuperLogLog] {
+        let estimate = hll.len_with(estimator);
+        assert!((estimate - 1_000_000.0).abs() < 80_000.0);
+    }
+}
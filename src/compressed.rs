@@ -0,0 +1,314 @@
+//! A compact representation of `HyperLogLog` counters for cold storage.
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use crate::{Error, HashConfig, HyperLogLog, MAX_PRECISION, MIN_PRECISION};
+
+/// A `HyperLogLog` counter whose registers are packed and run-length
+/// encoded, returned by [`HyperLogLog::compress`].
+///
+/// Registers are first packed into 6 bits each, like in
+/// [`PackedRegisters`](crate::store::PackedRegisters), so that 4 registers
+/// fit in 3 bytes. The packed bytes are then stored as a sequence of blocks,
+/// each starting with a varint whose lowest bit is set for a run of zero
+/// bytes, and clear for literal bytes, which follow it. The remaining bits
+/// are the number of bytes in the block, minus one. Sparse counters shrink
+/// to a few bytes, and dense counters take about 6 bits per register.
+///
+/// Compressed counters can't be updated, but they can be merged into a
+/// `HyperLogLog` counter without being decompressed first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressedHyperLogLog {
+    p: u8,
+    hash: HashConfig,
+    data: Vec<u8>,
+}
+
+crate::hash::zeroize_on_drop!(CompressedHyperLogLog);
+
+impl CompressedHyperLogLog {
+    /// Return the precision, i.e. the base-2 logarithm of the number of
+    /// registers.
+    #[must_use]
+    pub fn precision(&self) -> u8 {
+        self.p
+    }
+
+    /// Return the hash function used to map values to registers.
+    #[must_use]
+    pub fn hash_config(&self) -> HashConfig {
+        self.hash
+    }
+
+    /// Return the memory used by the compressed counter, in bytes.
+    #[must_use]
+    pub fn size_in_bytes(&self) -> usize {
+        size_of::<Self>() + self.data.capacity()
+    }
+
+    /// Return the uncompressed counter.
+    ///
+    /// Return an error if the encoded registers are invalid, which can only
+    /// happen if the compressed counter was deserialized from corrupted
+    /// data.
+    pub fn decompress(&self) -> Result<HyperLogLog, Error> {
+        self.check_precision()?;
+        let mut registers = Vec::with_capacity(1usize << self.p);
+        self.decode(|value, len| registers.resize(registers.len() + len, value))?;
+        HyperLogLog::from_registers_with_hash_config(self.p, self.hash, registers)
+    }
+
+    // The precision is only checked when the counter is created, so it can
+    // be invalid after deserialization.
+    fn check_precision(&self) -> Result<(), Error> {
+        if !(MIN_PRECISION..=MAX_PRECISION).contains(&self.p) {
+            return Err(Error::InvalidPrecision);
+        }
+        Ok(())
+    }
+
+    // Call `f` with the value and length of each run of registers, checking
+    // that the runs cover exactly `2^p` registers with valid values.
+    fn decode(&self, f: impl FnMut(u8, usize)) -> Result<(), Error> {
+        self.check_precision()?;
+        let packed_len = (1usize << self.p) / 4 * 3;
+        let mut unpacker = Unpacker {
+            f,
+            max_rho: 64 - self.p + 1,
+            acc: 0,
+            bits: 0,
+        };
+        let mut data = self.data.iter().copied();
+        let mut total = 0;
+        while data.len() > 0 {
+            let header = read_varint(&mut data).ok_or(Error::InvalidRegisterCount)?;
+            let len = usize::try_from(header >> 1)
+                .ok()
+                .and_then(|x| x.checked_add(1))
+                .filter(|&len| len <= packed_len - total)
+                .ok_or(Error::InvalidRegisterCount)?;
+            if header & 1 == 1 {
+                unpacker.push_zeros(len)?;
+            } else {
+                for _ in 0..len {
+                    unpacker.push(data.next().ok_or(Error::InvalidRegisterCount)?)?;
+                }
+            }
+            total += len;
+        }
+        if total != packed_len {
+            return Err(Error::InvalidRegisterCount);
+        }
+        Ok(())
+    }
+}
+
+// Unpack 6-bit registers from a stream of bytes, emitting runs of registers.
+struct Unpacker<F> {
+    f: F,
+    max_rho: u8,
+    acc: u32,
+    bits: usize,
+}
+
+impl<F: FnMut(u8, usize)> Unpacker<F> {
+    fn emit(&mut self, value: u8, len: usize) -> Result<(), Error> {
+        if value > self.max_rho {
+            return Err(Error::InvalidRegisterValue);
+        }
+        (self.f)(value, len);
+        Ok(())
+    }
+
+    fn push(&mut self, byte: u8) -> Result<(), Error> {
+        self.acc |= (byte as u32) << self.bits;
+        self.bits += 8;
+        while self.bits >= 6 {
+            self.emit((self.acc & 0x3f) as u8, 1)?;
+            self.acc >>= 6;
+            self.bits -= 6;
+        }
+        Ok(())
+    }
+
+    fn push_zeros(&mut self, n: usize) -> Result<(), Error> {
+        let mut zero_bits = n * 8;
+        if self.bits > 0 {
+            self.emit(self.acc as u8, 1)?;
+            zero_bits -= 6 - self.bits;
+        }
+        if zero_bits >= 6 {
+            self.emit(0, zero_bits / 6)?;
+        }
+        self.acc = 0;
+        self.bits = zero_bits % 6;
+        Ok(())
+    }
+}
+
+impl HyperLogLog {
+    /// Return a compressed copy of the counter, for storage.
+    #[must_use]
+    pub fn compress(&self) -> CompressedHyperLogLog {
+        // Shorter runs of zeros are cheaper to store as literals.
+        const MIN_ZERO_RUN: usize = 3;
+
+        let mut packed = Vec::with_capacity(self.m / 4 * 3);
+        for r in self.M.chunks_exact(4) {
+            packed.extend_from_slice(&[
+                r[0] | r[1] << 6,
+                r[1] >> 2 | r[2] << 4,
+                r[2] >> 4 | r[3] << 2,
+            ]);
+        }
+        let mut data = vec![];
+        let flush_literal = |data: &mut Vec<u8>, literal: &[u8]| {
+            if !literal.is_empty() {
+                write_varint(data, ((literal.len() - 1) as u64) << 1);
+                data.extend_from_slice(literal);
+            }
+        };
+        let (mut start, mut i) = (0, 0);
+        while i < packed.len() {
+            let zeros = packed[i..].iter().take_while(|&&x| x == 0).count();
+            if zeros >= MIN_ZERO_RUN {
+                flush_literal(&mut data, &packed[start..i]);
+                write_varint(&mut data, ((zeros - 1) as u64) << 1 | 1);
+                start = i + zeros;
+            }
+            i += zeros.max(1);
+        }
+        flush_literal(&mut data, &packed[start..]);
+        CompressedHyperLogLog {
+            p: self.p,
+            hash: self.hash,
+            data,
+        }
+    }
+
+    /// Merge a compressed counter into the current one, without
+    /// decompressing it first.
+    ///
+    /// Return an error if the counters don't have the same parameters and
+    /// hash function, or on the first invalid run of registers. Since merging
+    /// is idempotent, the runs applied before it can be safely kept.
+    pub fn merge_from_compressed(&mut self, src: &CompressedHyperLogLog) -> Result<(), Error> {
+        if src.p != self.p {
+            return Err(Error::IncompatiblePrecision);
+        }
+        self.check_hash(src.hash)?;
+        let mut j = 0;
+        src.decode(|value, len| {
+            if value > 0 {
+                for k in j..j + len {
+                    if value > self.M[k] {
+                        Arc::make_mut(&mut self.M)[k] = value;
+                        self.changes.record(k, self.m);
+                    }
+                }
+            }
+            j += len;
+        })
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut x: u64) {
+    while x >= 0x80 {
+        out.push(x as u8 | 0x80);
+        x >>= 7;
+    }
+    out.push(x as u8);
+}
+
+fn read_varint(data: &mut impl Iterator<Item = u8>) -> Option<u64> {
+    let mut x = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = data.next()?;
+        x |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(x);
+        }
+    }
+    None
+}
+
+#[test]
+fn compressed_test_roundtrip() {
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1);
+    let compressed = hll.compress();
+    assert!(compressed.data.len() < 8);
+    assert_eq!(compressed.decompress().unwrap(), hll);
+
+    hll.insert_all(0..1000u32);
+    let compressed = hll.compress();
+    assert!(compressed.data.len() < 4000);
+    assert_eq!(compressed.decompress().unwrap(), hll);
+
+    hll.insert_all(0..1_000_000u32);
+    let compressed = hll.compress();
+    // Packed registers take 6 bits, instead of a byte each.
+    assert!(compressed.data.len() < hll.num_registers() * 4 / 5);
+    assert_eq!(compressed.decompress().unwrap(), hll);
+    assert_eq!(compressed.precision(), 14);
+    assert_eq!(compressed.hash_config(), hll.hash_config());
+}
+
+#[test]
+fn compressed_test_merge() {
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1);
+    hll.insert_all(0..5000u32);
+    let mut hll2 = HyperLogLog::new_from_template(&hll);
+    hll2.insert_all(2500..7500u32);
+    let mut expected = hll.clone();
+    expected.merge(&hll2);
+    hll.merge_from_compressed(&hll2.compress()).unwrap();
+    assert_eq!(hll, expected);
+
+    let other = HyperLogLog::new_deterministic(0.001, 2).compress();
+    assert_eq!(
+        hll.merge_from_compressed(&other),
+        Err(Error::IncompatibleSeed)
+    );
+    let other = HyperLogLog::new_deterministic(0.04, 1).compress();
+    assert_eq!(
+        hll.merge_from_compressed(&other),
+        Err(Error::IncompatiblePrecision)
+    );
+}
+
+#[test]
+fn compressed_test_invalid() {
+    let hll = HyperLogLog::new_deterministic(HyperLogLog::error_rate_for_precision(4), 1);
+    let mut compressed = hll.compress();
+    compressed.data = vec![10 << 1 | 1];
+    assert_eq!(
+        compressed.decompress().unwrap_err(),
+        Error::InvalidRegisterCount
+    );
+    compressed.data = vec![12 << 1 | 1];
+    assert_eq!(
+        compressed.decompress().unwrap_err(),
+        Error::InvalidRegisterCount
+    );
+    compressed.data = vec![1, 0x80];
+    assert_eq!(
+        compressed.decompress().unwrap_err(),
+        Error::InvalidRegisterCount
+    );
+    compressed.data = vec![0, 62, 10 << 1 | 1];
+    assert_eq!(
+        compressed.decompress().unwrap_err(),
+        Error::InvalidRegisterValue
+    );
+    compressed.data = vec![0, 61, 10 << 1 | 1];
+    assert_eq!(compressed.decompress().unwrap().registers()[0], 61);
+    let saturated = HyperLogLog::from_parts(4, hll.hash_config(), vec![61; 16]);
+    assert_eq!(saturated.compress().decompress().unwrap(), saturated);
+    compressed.p = 64;
+    assert_eq!(
+        compressed.decompress().unwrap_err(),
+        Error::InvalidPrecision
+    );
+}
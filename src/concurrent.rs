@@ -0,0 +1,178 @@
+//! A sharded `HyperLogLog` counter for write-heavy concurrent workloads.
+
+use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+
+use crate::HyperLogLog;
+
+static NEXT_THREAD_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // Threads are numbered in the order they first insert into any counter.
+    // This is the only per-thread state, whatever the number of counters.
+    static THREAD_INDEX: usize = NEXT_THREAD_INDEX.fetch_add(1, Ordering::Relaxed);
+}
+
+// Keep shards on separate cache lines to avoid false sharing.
+#[derive(Debug)]
+#[repr(align(64))]
+struct Shard(Mutex<HyperLogLog>);
+
+/// A HyperLogLog counter split into shards sharing the same parameters and
+/// seed. Each thread inserts into its own shard, and shards are only merged
+/// when the cardinality is requested.
+///
+/// Threads are numbered in the order they first insert into a counter, and
+/// each thread uses the shard of its number modulo the number of shards.
+/// Threads started together, such as the workers of a pool, therefore use
+/// different shards as long as there are no more of them than shards.
+///
+/// Shards are still protected by a mutex, which lets `len()` and
+/// `to_hyperloglog()` read them while other threads insert. Inserting locks
+/// the shard of the current thread, which, without contention, costs a
+/// single atomic compare-and-swap; [`insert_all`](Self::insert_all) only
+/// locks it once for all the values.
+#[derive(Debug)]
+pub struct ConcurrentHyperLogLog {
+    shards: Vec<Shard>,
+}
+
+impl ConcurrentHyperLogLog {
+    /// Create a new `ConcurrentHyperLogLog` counter with the given error rate
+    /// and seed, and one shard per available CPU.
+    #[must_use]
+    pub fn new_deterministic(error_rate: f64, seed: u128) -> Self {
+        let shards = thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards(&HyperLogLog::new_deterministic(error_rate, seed), shards)
+    }
+
+    /// Create a new `ConcurrentHyperLogLog` counter with the given error rate,
+    /// a random seed, and one shard per available CPU. Requires the `random`
+    /// feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(error_rate: f64) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(error_rate, seed)
+    }
+
+    /// Create a new, empty `ConcurrentHyperLogLog` counter with the given
+    /// number of shards and the same parameters as `template`.
+    #[must_use]
+    pub fn with_shards(template: &HyperLogLog, shards: usize) -> Self {
+        assert!(shards > 0);
+        let shards = (0..shards)
+            .map(|_| Shard(Mutex::new(HyperLogLog::new_from_template(template))))
+            .collect();
+        ConcurrentHyperLogLog { shards }
+    }
+
+    fn shard(&self) -> MutexGuard<'_, HyperLogLog> {
+        let idx = THREAD_INDEX.with(|&idx| idx) % self.shards.len();
+        self.shards[idx].0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Insert a new value into the counter.
+    pub fn insert<V: Hash>(&self, value: &V) {
+        let mut shard = self.shard();
+        let x = shard.hash_value(value);
+        shard.insert_by_hash_value(x);
+    }
+
+    /// Insert a new u64 value into the counter.
+    pub fn insert_by_hash_value(&self, x: u64) {
+        self.shard().insert_by_hash_value(x);
+    }
+
+    /// Insert all the values of an iterator into the counter, locking the
+    /// shard only once.
+    pub fn insert_all<I>(&self, values: I)
+    where
+        I: IntoIterator,
+        I::Item: Hash,
+    {
+        self.shard().insert_all(values);
+    }
+
+    /// Return a `HyperLogLog` counter with the union of all the shards.
+    #[must_use]
+    pub fn to_hyperloglog(&self) -> HyperLogLog {
+        let shards: Vec<_> = self
+            .shards
+            .iter()
+            .map(|shard| shard.0.lock().unwrap_or_else(|e| e.into_inner()))
+            .collect();
+        let mut hll = HyperLogLog::new_from_template(&shards[0]);
+        hll.merge_many(&shards.iter().map(|shard| &**shard).collect::<Vec<_>>());
+        hll
+    }
+
+    /// Return the cardinality of the counter, merging all the shards.
+    #[must_use]
+    pub fn len(&self) -> f64 {
+        self.to_hyperloglog().len()
+    }
+
+    /// Return `true` if the counter is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.shards
+            .iter()
+            .all(|shard| shard.0.lock().unwrap_or_else(|e| e.into_inner()).is_empty())
+    }
+
+    /// Wipe the counter.
+    pub fn clear(&self) {
+        for shard in &self.shards {
+            shard.0.lock().unwrap_or_else(|e| e.into_inner()).clear();
+        }
+    }
+}
+
+#[test]
+fn concurrent_test_inserts() {
+    let template = HyperLogLog::new_deterministic(0.001, 1);
+    let hll = ConcurrentHyperLogLog::with_shards(&template, 4);
+    assert!(hll.is_empty());
+    thread::scope(|s| {
+        for t in 0..8u32 {
+            let hll = &hll;
+            s.spawn(move || {
+                for i in 0..10_000u32 {
+                    hll.insert(&(t * 5_000 + i));
+                }
+            });
+        }
+    });
+    let mut expected = HyperLogLog::new_from_template(&template);
+    expected.insert_all(0..45_000u32);
+    assert_eq!(hll.to_hyperloglog().registers(), expected.registers());
+    assert!((hll.len() - 45_000.0).abs() < 2_000.0);
+    hll.clear();
+    assert!(hll.is_empty());
+}
+
+#[test]
+fn concurrent_test_shard_assignment() {
+    let template = HyperLogLog::new_deterministic(0.001, 1);
+    let hll = ConcurrentHyperLogLog::with_shards(&template, 4);
+    // All the values inserted by a thread go to the same shard.
+    thread::scope(|s| {
+        s.spawn(|| hll.insert_all(0..1_000u32));
+    });
+    let used = hll
+        .shards
+        .iter()
+        .filter(|shard| !shard.0.lock().unwrap().is_empty())
+        .count();
+    assert_eq!(used, 1);
+    thread::scope(|s| {
+        for i in 0..8u32 {
+            let hll = &hll;
+            s.spawn(move || hll.insert(&(1_000 + i)));
+        }
+    });
+    assert!((hll.len() - 1_008.0).abs() < 20.0);
+}
@@ -0,0 +1,132 @@
+//! State-based CRDT traits.
+//!
+//! `HyperLogLog` counters form a join-semilattice: merging takes the maximum
+//! of each register, which is idempotent, commutative and associative. These
+//! traits allow counters to be used by frameworks replicating such states.
+
+use std::cmp::Ordering;
+
+use crate::{Error, HyperLogLog, SnapshotToken};
+
+/// A state-based (convergent) replicated data type.
+pub trait CvRdt {
+    /// The error returned when two states cannot be merged.
+    type Error;
+
+    /// Check that `other` can be merged into the current state.
+    fn validate_merge(&self, other: &Self) -> Result<(), Self::Error>;
+
+    /// Merge `other` into the current state. This operation must be
+    /// idempotent, commutative and associative.
+    fn merge(&mut self, other: &Self);
+
+    /// Compare two states according to the partial order of the semilattice:
+    /// `Less` if merging the current state into `other` would leave it
+    /// unchanged, `Greater` for the converse, and `None` if the states are
+    /// concurrent or not comparable.
+    fn partial_cmp_state(&self, other: &Self) -> Option<Ordering>;
+}
+
+/// A delta-state replicated data type, able to ship only the part of its
+/// state that changed since a given point in time.
+pub trait DeltaCrdt: CvRdt {
+    /// A point in the history of the state.
+    type Token;
+    /// A fragment of the state.
+    type Delta;
+
+    /// Return a token representing the current state.
+    fn snapshot(&self) -> Self::Token;
+
+    /// Return a delta containing at least the changes since `token`.
+    fn delta_since(&self, token: Self::Token) -> Self::Delta;
+
+    /// Merge a delta into the current state.
+    fn merge_delta(&mut self, delta: &Self::Delta) -> Result<(), Self::Error>;
+}
+
+impl CvRdt for HyperLogLog {
+    type Error = Error;
+
+    fn validate_merge(&self, other: &Self) -> Result<(), Error> {
+        self.check_compatible(other)
+    }
+
+    fn merge(&mut self, other: &Self) {
+        HyperLogLog::merge(self, other)
+    }
+
+    fn partial_cmp_state(&self, other: &Self) -> Option<Ordering> {
+        if !self.is_compatible(other) {
+            return None;
+        }
+        let (mut le, mut ge) = (true, true);
+        for (&mir, &other_mir) in self.M.iter().zip(other.M.iter()) {
+            le &= mir <= other_mir;
+            ge &= mir >= other_mir;
+        }
+        match (le, ge) {
+            (true, true) => Some(Ordering::Equal),
+            (true, false) => Some(Ordering::Less),
+            (false, true) => Some(Ordering::Greater),
+            (false, false) => None,
+        }
+    }
+}
+
+impl DeltaCrdt for HyperLogLog {
+    type Token = SnapshotToken;
+    type Delta = Vec<(u32, u8)>;
+
+    fn snapshot(&self) -> SnapshotToken {
+        HyperLogLog::snapshot(self)
+    }
+
+    fn delta_since(&self, token: SnapshotToken) -> Vec<(u32, u8)> {
+        self.changes_since(token).collect()
+    }
+
+    fn merge_delta(&mut self, delta: &Vec<(u32, u8)>) -> Result<(), Error> {
+        self.merge_sparse(delta.iter().copied())
+    }
+}
+
+#[test]
+fn crdt_test_semilattice() {
+    let mut a = HyperLogLog::new_deterministic(0.001, 1);
+    let mut b = HyperLogLog::new_from_template(&a);
+    a.insert_all(0..100u32);
+    b.insert_all(50..200u32);
+    assert_eq!(a.partial_cmp_state(&b), None);
+
+    let mut ab = a.clone();
+    CvRdt::merge(&mut ab, &b);
+    let mut ba = b.clone();
+    CvRdt::merge(&mut ba, &a);
+    assert_eq!(ab.partial_cmp_state(&ba), Some(Ordering::Equal));
+    assert_eq!(a.partial_cmp_state(&ab), Some(Ordering::Less));
+    assert_eq!(ab.partial_cmp_state(&b), Some(Ordering::Greater));
+    CvRdt::merge(&mut ab, &b);
+    assert_eq!(ab.partial_cmp_state(&ba), Some(Ordering::Equal));
+
+    let other = HyperLogLog::new_deterministic(0.001, 2);
+    assert_eq!(a.validate_merge(&other), Err(Error::IncompatibleSeed));
+    assert_eq!(a.partial_cmp_state(&other), None);
+}
+
+#[test]
+fn crdt_test_delta() {
+    let mut a = HyperLogLog::new_deterministic(0.001, 1);
+    let mut replica = HyperLogLog::new_from_template(&a);
+    a.track_changes();
+    a.insert_all(0..100u32);
+    let token = DeltaCrdt::snapshot(&a);
+    replica
+        .merge_delta(&a.delta_since(SnapshotToken(0)))
+        .unwrap();
+    a.insert_all(100..150u32);
+    let delta = a.delta_since(token);
+    assert!(delta.len() <= 50);
+    replica.merge_delta(&delta).unwrap();
+    assert_eq!(a.partial_cmp_state(&replica), Some(Ordering::Equal));
+}
@@ -0,0 +1,144 @@
+//! A `HyperLogLog` counter where older values count less.
+
+use std::hash::Hash;
+
+use crate::{HyperLogLog, WindowedHyperLogLog};
+
+/// A counter estimating an exponentially time-decayed number of distinct
+/// values: each distinct value is weighted by `0.5^(age/half_life)`, where
+/// `age` is the time elapsed since it was last seen.
+///
+/// Values are inserted into fixed-duration time buckets. The number of
+/// values last seen in a given bucket is the difference between the
+/// cardinalities of the unions of the buckets up to it and up to the
+/// previous one, and is weighted by the age of the middle of the bucket.
+/// Values older than the oldest bucket are forgotten.
+///
+/// Timestamps are arbitrary `u64` units, such as seconds.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecayedHyperLogLog {
+    half_life: f64,
+    window: WindowedHyperLogLog,
+}
+
+impl DecayedHyperLogLog {
+    /// Create a new `DecayedHyperLogLog` counter with the given error rate,
+    /// half-life and seed.
+    ///
+    /// Buckets last a quarter of the half-life, and values are kept for 10
+    /// half-lives, after which their weight is below 0.1%.
+    #[must_use]
+    pub fn new_deterministic(error_rate: f64, half_life: u64, seed: u128) -> Self {
+        Self::with_buckets(
+            &HyperLogLog::new_deterministic(error_rate, seed),
+            half_life,
+            40,
+            (half_life / 4).max(1),
+        )
+    }
+
+    /// Create a new `DecayedHyperLogLog` counter with the given error rate,
+    /// half-life and a random seed. Requires the `random` feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(error_rate: f64, half_life: u64) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(error_rate, half_life, seed)
+    }
+
+    /// Create a new, empty `DecayedHyperLogLog` counter with the given
+    /// half-life, number of buckets and bucket duration, whose buckets have
+    /// the same parameters as `template`.
+    #[must_use]
+    pub fn with_buckets(
+        template: &HyperLogLog,
+        half_life: u64,
+        buckets: usize,
+        bucket_duration: u64,
+    ) -> Self {
+        assert!(half_life > 0);
+        DecayedHyperLogLog {
+            half_life: half_life as f64,
+            window: WindowedHyperLogLog::with_buckets(template, buckets, bucket_duration),
+        }
+    }
+
+    /// Insert a new value, seen at `timestamp`.
+    pub fn insert_at<V: Hash>(&mut self, value: &V, timestamp: u64) {
+        self.window.insert(value, timestamp);
+    }
+
+    /// Insert a new u64 value, seen at `timestamp`.
+    pub fn insert_by_hash_value_at(&mut self, x: u64, timestamp: u64) {
+        self.window.insert_by_hash_value(x, timestamp);
+    }
+
+    /// Return the decayed number of distinct values at time `now`.
+    #[must_use]
+    pub fn decayed_len(&self, now: u64) -> f64 {
+        let newest = match self.window.current_bucket() {
+            Some(newest) => newest,
+            None => return 0.0,
+        };
+        let d = self.window.bucket_duration();
+        let mut union = HyperLogLog::new_from_template(self.window.buckets().next().unwrap());
+        let (mut sum, mut prev) = (0.0, 0.0);
+        for (age, bucket) in self.window.buckets().rev().enumerate() {
+            let Some(index) = newest.checked_sub(age as u64) else {
+                break;
+            };
+            union.merge(bucket);
+            let len = union.len();
+            let middle = index as f64 * d as f64 + d as f64 / 2.0;
+            let elapsed = (now as f64 - middle).max(0.0);
+            sum += (len - prev) * 0.5f64.powf(elapsed / self.half_life);
+            prev = len;
+        }
+        sum.max(0.0)
+    }
+
+    /// Return the number of distinct values in all the buckets, without
+    /// decay.
+    #[must_use]
+    pub fn len(&self) -> f64 {
+        self.window.len()
+    }
+
+    /// Return `true` if the counter is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.window.is_empty()
+    }
+
+    /// Wipe the counter.
+    pub fn clear(&mut self) {
+        self.window.clear();
+    }
+}
+
+#[test]
+fn decayed_test_decay() {
+    let mut hll = DecayedHyperLogLog::new_deterministic(0.001, 100, 1);
+    assert!(hll.decayed_len(0) == 0.0);
+    assert!(hll.is_empty());
+    for i in 0..10_000u32 {
+        hll.insert_at(&i, 0);
+    }
+    assert!((hll.decayed_len(12) - 10_000.0).abs() < 500.0);
+    assert!((hll.decayed_len(112) - 5_000.0).abs() < 250.0);
+    assert!((hll.decayed_len(212) - 2_500.0).abs() < 125.0);
+
+    // Values seen again only count once, with their latest timestamp.
+    for i in 0..5_000u32 {
+        hll.insert_at(&i, 200);
+    }
+    let expected = 5_000.0 * 0.5f64.powf(0.755) + 5_000.0 * 0.5f64.powf(2.755);
+    assert!((hll.decayed_len(288) - expected).abs() < 250.0);
+    assert!((hll.len() - 10_000.0).abs() < 500.0);
+
+    hll.insert_at(&0u32, 10_000);
+    assert!((hll.decayed_len(10_012) - 1.0).abs() < 0.1);
+    hll.clear();
+    assert!(hll.is_empty());
+}
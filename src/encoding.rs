@@ -0,0 +1,283 @@
+//! A compact binary encoding of `HyperLogLog` counters.
+//!
+//! The encoding is a 19-byte header followed by the registers, one byte
+//! each:
+//!
+//! - the format version, currently `1`
+//! - the hash function: `0` for SipHash-1-3, `1` for XXH3-64, `2` for
+//!   MurmurHash3
+//! - the seed, as a 128-bit little-endian integer
+//! - the precision
+//!
+//! Counters with the same precision have the same encoded size.
+//!
+//! A shard of a counter is encoded with the same header, followed by the
+//! index of the shard and the number of shards, as 32-bit little-endian
+//! integers, and the registers of the shard.
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use crate::{Error, HashConfig, HyperLogLog};
+
+const FORMAT_VERSION: u8 = 1;
+const HEADER_LEN: usize = 19;
+
+impl HyperLogLog {
+    /// Encode the counter into a compact binary representation, including
+    /// its parameters, that can be stored or sent to other processes, for
+    /// example as the partial state of a distributed aggregation.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::encoded_len(self.p));
+        self.write_header(&mut bytes);
+        bytes.extend_from_slice(&self.M);
+        bytes
+    }
+
+    /// Decode a counter encoded with [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let (hash, p) = Self::decode_header(bytes)?;
+        Self::from_registers_with_hash_config(p, hash, bytes[HEADER_LEN..].to_vec())
+    }
+
+    /// Merge a counter encoded with [`to_bytes`](Self::to_bytes) into the
+    /// current one, without decoding it into a new counter. This is how
+    /// columns of encoded counters are aggregated.
+    ///
+    /// Return an error if the encoding is malformed, or if the counters are
+    /// not compatible. The current counter is left unchanged on error.
+    pub fn merge_from_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let (hash, p) = Self::decode_header(bytes)?;
+        if p != self.p {
+            return Err(Error::IncompatiblePrecision);
+        }
+        self.hash.check_compatible(hash)?;
+        let registers = &bytes[HEADER_LEN..];
+        if registers.len() != self.m {
+            return Err(Error::InvalidRegisterCount);
+        }
+        if registers.iter().any(|&x| x > 64 - self.p + 1) {
+            return Err(Error::InvalidRegisterValue);
+        }
+        let (changes, m) = (&mut self.changes, self.m);
+        crate::simd::max_into(&mut Arc::make_mut(&mut self.M)[..], registers, |i| {
+            changes.record(i, m)
+        });
+        Ok(())
+    }
+
+    /// Split the registers into `n` contiguous ranges, and encode each of
+    /// them with [`shard_at`](Self::shard_at).
+    ///
+    /// Panics if `n` is zero or larger than the number of registers.
+    #[must_use]
+    pub fn shard(&self, n: usize) -> Vec<Vec<u8>> {
+        (0..n).map(|idx| self.shard_at(idx, n)).collect()
+    }
+
+    /// Encode the registers of the shard `idx`, out of `n` contiguous
+    /// ranges of registers, so that large counters can be transferred or
+    /// updated piecewise with [`merge_shard`](Self::merge_shard).
+    ///
+    /// Panics if `n` is zero or larger than the number of registers, or if
+    /// `idx` is not lower than `n`.
+    #[must_use]
+    pub fn shard_at(&self, idx: usize, n: usize) -> Vec<u8> {
+        assert!(n > 0 && n <= self.m && idx < n);
+        let range = Self::shard_range(self.m, idx, n);
+        let mut bytes = Vec::with_capacity(HEADER_LEN + 8 + range.len());
+        self.write_header(&mut bytes);
+        bytes.extend_from_slice(&(idx as u32).to_le_bytes());
+        bytes.extend_from_slice(&(n as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.M[range]);
+        bytes
+    }
+
+    /// Merge the shard `idx` of a counter, encoded with
+    /// [`shard_at`](Self::shard_at), into the current counter.
+    ///
+    /// Return an error if the shard is malformed, doesn't match `idx`, or
+    /// if the counters are not compatible. The current counter is left
+    /// unchanged on error.
+    pub fn merge_shard(&mut self, idx: usize, bytes: &[u8]) -> Result<(), Error> {
+        let (hash, p) = Self::decode_header(bytes)?;
+        if p != self.p {
+            return Err(Error::IncompatiblePrecision);
+        }
+        self.hash.check_compatible(hash)?;
+        let read_u32 = |i: usize| {
+            bytes
+                .get(i..i + 4)
+                .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]) as usize)
+                .ok_or(Error::InvalidEncoding)
+        };
+        let (shard_idx, n) = (read_u32(HEADER_LEN)?, read_u32(HEADER_LEN + 4)?);
+        if n == 0 || n > self.m || shard_idx >= n {
+            return Err(Error::InvalidEncoding);
+        }
+        if idx != shard_idx {
+            return Err(Error::InvalidRegisterIndex);
+        }
+        let range = Self::shard_range(self.m, idx, n);
+        let registers = &bytes[HEADER_LEN + 8..];
+        if registers.len() != range.len() {
+            return Err(Error::InvalidRegisterCount);
+        }
+        if registers.iter().any(|&x| x > 64 - self.p + 1) {
+            return Err(Error::InvalidRegisterValue);
+        }
+        let (changes, m, start) = (&mut self.changes, self.m, range.start);
+        crate::simd::max_into(&mut Arc::make_mut(&mut self.M)[range], registers, |i| {
+            changes.record(start + i, m)
+        });
+        Ok(())
+    }
+
+    /// Return the size of the binary encoding of counters with precision
+    /// `p`.
+    #[must_use]
+    pub fn encoded_len(p: u8) -> usize {
+        HEADER_LEN + (1usize << p)
+    }
+
+    fn write_header(&self, bytes: &mut Vec<u8>) {
+        let kind = match self.hash {
+            HashConfig::SipHash13 { .. } => 0,
+            HashConfig::Xxh3 { .. } => 1,
+            HashConfig::Murmur3 { .. } => 2,
+        };
+        bytes.push(FORMAT_VERSION);
+        bytes.push(kind);
+        bytes.extend_from_slice(&self.hash.seed().to_le_bytes());
+        bytes.push(self.p);
+    }
+
+    // Computed in 64 bits, as `idx * m` can overflow a 32-bit `usize`.
+    fn shard_range(m: usize, idx: usize, n: usize) -> std::ops::Range<usize> {
+        let bound = |i: usize| (i as u64 * m as u64 / n as u64) as usize;
+        bound(idx)..bound(idx + 1)
+    }
+
+    fn decode_header(bytes: &[u8]) -> Result<(HashConfig, u8), Error> {
+        if bytes.len() < HEADER_LEN || bytes[0] != FORMAT_VERSION {
+            return Err(Error::InvalidEncoding);
+        }
+        let mut seed = [0u8; 16];
+        seed.copy_from_slice(&bytes[2..18]);
+        let seed = u128::from_le_bytes(seed);
+        let hash = match bytes[1] {
+            0 => HashConfig::SipHash13 { seed },
+            1 => HashConfig::Xxh3 {
+                seed: u64::try_from(seed).map_err(|_| Error::InvalidEncoding)?,
+            },
+            2 => HashConfig::Murmur3 {
+                seed: u32::try_from(seed).map_err(|_| Error::InvalidEncoding)?,
+            },
+            _ => return Err(Error::InvalidEncoding),
+        };
+        hash.check_supported()?;
+        Ok((hash, bytes[18]))
+    }
+}
+
+#[test]
+fn encoding_test_roundtrip() {
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1 << 100 | 42);
+    hll.insert_all(0..10_000u32);
+    let bytes = hll.to_bytes();
+    assert_eq!(bytes.len(), HyperLogLog::encoded_len(hll.precision()));
+    assert_eq!(HyperLogLog::from_bytes(&bytes), Ok(hll.clone()));
+
+    let mut hll = HyperLogLog::new_with_hash_config(0.001, HashConfig::Murmur3 { seed: 7 });
+    hll.insert(&1u32);
+    assert_eq!(HyperLogLog::from_bytes(&hll.to_bytes()), Ok(hll.clone()));
+
+    let mut bytes = hll.to_bytes();
+    assert_eq!(
+        HyperLogLog::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(Error::InvalidRegisterCount)
+    );
+    assert_eq!(
+        HyperLogLog::from_bytes(&bytes[..10]),
+        Err(Error::InvalidEncoding)
+    );
+    bytes[1] = 3;
+    assert_eq!(HyperLogLog::from_bytes(&bytes), Err(Error::InvalidEncoding));
+}
+
+#[test]
+fn encoding_test_merge_from_bytes() {
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1);
+    let mut hll2 = HyperLogLog::new_from_template(&hll);
+    hll.insert_all(0..10_000u32);
+    hll2.insert_all(5_000..20_000u32);
+    let expected = hll.union(&hll2);
+    hll.merge_from_bytes(&hll2.to_bytes()).unwrap();
+    assert_eq!(hll, expected);
+
+    let other = HyperLogLog::new_deterministic(0.001, 2);
+    assert_eq!(
+        hll.merge_from_bytes(&other.to_bytes()),
+        Err(Error::IncompatibleSeed)
+    );
+    let other = HyperLogLog::new_deterministic(0.04, 1);
+    assert_eq!(
+        hll.merge_from_bytes(&other.to_bytes()),
+        Err(Error::IncompatiblePrecision)
+    );
+    let mut bytes = hll2.to_bytes();
+    bytes[HEADER_LEN] = 64;
+    assert_eq!(
+        hll.merge_from_bytes(&bytes),
+        Err(Error::InvalidRegisterValue)
+    );
+    assert_eq!(hll, expected);
+}
+
+#[test]
+fn encoding_test_shards() {
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1);
+    hll.insert_all(0..50_000u32);
+    let shards = hll.shard(7);
+    assert_eq!(shards.len(), 7);
+    let mut copy = HyperLogLog::new_from_template(&hll);
+    for (idx, shard) in shards.iter().enumerate() {
+        copy.merge_shard(idx, shard).unwrap();
+    }
+    assert_eq!(copy, hll);
+
+    let mut other = HyperLogLog::new_from_template(&hll);
+    assert_eq!(
+        other.merge_shard(1, &shards[0]),
+        Err(Error::InvalidRegisterIndex)
+    );
+    assert_eq!(
+        other.merge_shard(7, &shards[0]),
+        Err(Error::InvalidRegisterIndex)
+    );
+    // Shards of the same length must not be mistaken for each other.
+    let shards = hll.shard(4);
+    assert_eq!(shards[0].len(), shards[1].len());
+    assert_eq!(
+        other.merge_shard(1, &shards[0]),
+        Err(Error::InvalidRegisterIndex)
+    );
+    let mut truncated = shards[2].clone();
+    truncated.pop();
+    assert_eq!(
+        other.merge_shard(2, &truncated),
+        Err(Error::InvalidRegisterCount)
+    );
+    let incompatible = HyperLogLog::new_deterministic(0.001, 2);
+    assert_eq!(
+        other.merge_shard(0, &incompatible.shard_at(0, 7)),
+        Err(Error::IncompatibleSeed)
+    );
+    assert!(other.is_empty());
+
+    let m = 1 << 26;
+    assert_eq!(HyperLogLog::shard_range(m, 0, m), 0..1);
+    assert_eq!(HyperLogLog::shard_range(m, m - 1, m), m - 1..m);
+    assert_eq!(HyperLogLog::shard_range(m, 2, 3), 44739242..m);
+}
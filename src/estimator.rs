@@ -0,0 +1,301 @@
+//! A common interface for mergeable cardinality sketches, and an exact
+//! counter implementing it.
+
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+use crate::classic::{LegacyHyperLogLog, Pcsa};
+use crate::{Error, HashConfig, HyperLogLog, HyperMinHash, KmvSketch};
+
+/// A mergeable sketch estimating the number of distinct values inserted
+/// into it.
+///
+/// Values are inserted as 64-bit hashes, so that sketches of different
+/// types can be fed from the same hashing code, swapped without rewriting
+/// call sites, or replaced with mocks in tests.
+pub trait CardinalityEstimator {
+    /// Insert the hash of a value into the sketch.
+    fn insert_hash(&mut self, x: u64);
+
+    /// Return the estimated number of distinct values in the sketch.
+    fn estimate(&self) -> f64;
+
+    /// Merge another sketch into the current one.
+    ///
+    /// Return an error if the sketches are not compatible.
+    fn merge(&mut self, other: &Self) -> Result<(), Error>;
+
+    /// Wipe the sketch.
+    fn clear(&mut self);
+}
+
+impl CardinalityEstimator for HyperLogLog {
+    fn insert_hash(&mut self, x: u64) {
+        self.insert_by_hash_value(x);
+    }
+
+    fn estimate(&self) -> f64 {
+        self.len()
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        self.try_merge(other)
+    }
+
+    fn clear(&mut self) {
+        HyperLogLog::clear(self)
+    }
+}
+
+impl CardinalityEstimator for HyperMinHash {
+    fn insert_hash(&mut self, x: u64) {
+        self.insert_by_hash_value(x);
+    }
+
+    fn estimate(&self) -> f64 {
+        self.len()
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        self.try_merge(other)
+    }
+
+    fn clear(&mut self) {
+        HyperMinHash::clear(self)
+    }
+}
+
+impl CardinalityEstimator for KmvSketch {
+    fn insert_hash(&mut self, x: u64) {
+        self.insert_by_hash_value(x);
+    }
+
+    fn estimate(&self) -> f64 {
+        self.len()
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        KmvSketch::merge(self, other)
+    }
+
+    fn clear(&mut self) {
+        KmvSketch::clear(self)
+    }
+}
+
+impl CardinalityEstimator for LegacyHyperLogLog {
+    fn insert_hash(&mut self, x: u64) {
+        self.insert_by_hash_value(x as u32);
+    }
+
+    fn estimate(&self) -> f64 {
+        self.len()
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        LegacyHyperLogLog::merge(self, other)
+    }
+
+    fn clear(&mut self) {
+        LegacyHyperLogLog::clear(self)
+    }
+}
+
+impl CardinalityEstimator for Pcsa {
+    fn insert_hash(&mut self, x: u64) {
+        self.insert_by_hash_value(x);
+    }
+
+    fn estimate(&self) -> f64 {
+        self.len()
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        Pcsa::merge(self, other)
+    }
+
+    fn clear(&mut self) {
+        Pcsa::clear(self)
+    }
+}
+
+/// An exact counter, keeping the hashes of all the distinct values inserted
+/// into it.
+///
+/// It uses memory proportional to the number of distinct values, but can
+/// replace a sketch in tests and low-volume code paths, and provides the
+/// ground truth to evaluate the accuracy of sketches fed with the same
+/// hashes. Values colliding on the same 64-bit hash are counted once.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExactCounter {
+    hash: HashConfig,
+    hashes: HashSet<u64>,
+}
+
+crate::hash::zeroize_on_drop!(ExactCounter);
+
+impl ExactCounter {
+    /// Create a new counter, hashing values with the given seed.
+    #[must_use]
+    pub fn new_deterministic(seed: u128) -> Self {
+        Self::new_with_hash_config(HashConfig::with_seed(seed))
+    }
+
+    /// Create a new counter, hashing values with the given hash function.
+    #[must_use]
+    pub fn new_with_hash_config(hash: HashConfig) -> Self {
+        ExactCounter {
+            hash,
+            hashes: HashSet::new(),
+        }
+    }
+
+    /// Create a new counter, hashing values with a random seed. Requires the
+    /// `random` feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new() -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(seed)
+    }
+
+    /// Insert a new value into the counter.
+    ///
+    /// Return `true` if it was not already present.
+    pub fn insert<V: Hash>(&mut self, value: &V) -> bool {
+        let mut sip = crate::hash::new_hasher(self.hash);
+        value.hash(&mut sip);
+        self.insert_by_hash_value(sip.finish())
+    }
+
+    /// Insert all the values of an iterator into the counter.
+    pub fn insert_all<I>(&mut self, values: I)
+    where
+        I: IntoIterator,
+        I::Item: Hash,
+    {
+        for value in values {
+            self.insert(&value);
+        }
+    }
+
+    /// Insert a new u64 value into the counter.
+    ///
+    /// Return `true` if it was not already present.
+    pub fn insert_by_hash_value(&mut self, x: u64) -> bool {
+        self.hashes.insert(x)
+    }
+
+    /// Return the number of distinct values in the counter.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    /// Return `true` if the counter is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Merge another counter into the current one.
+    ///
+    /// Return an error if the counters don't use the same hash function.
+    pub fn merge(&mut self, src: &ExactCounter) -> Result<(), Error> {
+        self.hash.check_compatible(src.hash)?;
+        self.hashes.extend(&src.hashes);
+        Ok(())
+    }
+
+    /// Wipe the counter.
+    pub fn clear(&mut self) {
+        self.hashes.clear();
+    }
+}
+
+#[cfg(feature = "random")]
+impl Default for ExactCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CardinalityEstimator for ExactCounter {
+    fn insert_hash(&mut self, x: u64) {
+        self.insert_by_hash_value(x);
+    }
+
+    fn estimate(&self) -> f64 {
+        self.len() as f64
+    }
+
+    fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        ExactCounter::merge(self, other)
+    }
+
+    fn clear(&mut self) {
+        ExactCounter::clear(self)
+    }
+}
+
+#[cfg(test)]
+fn count_halves<E: CardinalityEstimator + Clone>(mut a: E) -> f64 {
+    let mut b = a.clone();
+    let empty = a.estimate();
+    let hash = |x: u64| {
+        let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^ (x >> 31)
+    };
+    for x in 0..50_000u64 {
+        a.insert_hash(hash(x));
+        b.insert_hash(hash(x + 50_000));
+    }
+    a.merge(&b).unwrap();
+    let estimate = a.estimate();
+    a.clear();
+    assert!((a.estimate() - empty).abs() < f64::EPSILON);
+    estimate
+}
+
+#[test]
+fn estimator_test_implementations() {
+    let estimates = [
+        count_halves(HyperLogLog::new_deterministic(0.001, 1)),
+        count_halves(HyperMinHash::new_deterministic(0.001, 1)),
+        count_halves(KmvSketch::new_deterministic(4096, 1)),
+        count_halves(Pcsa::new_deterministic(12, 1)),
+        count_halves(LegacyHyperLogLog::new_deterministic(12, 1)),
+    ];
+    assert!((count_halves(ExactCounter::new_deterministic(1)) - 100_000.0).abs() < f64::EPSILON);
+    for &estimate in &estimates {
+        assert!((estimate - 100_000.0).abs() < 10_000.0);
+    }
+
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1);
+    let other = HyperLogLog::new_deterministic(0.001, 2);
+    assert_eq!(
+        CardinalityEstimator::merge(&mut hll, &other),
+        Err(Error::IncompatibleSeed)
+    );
+}
+
+#[cfg(any(feature = "with_bias_tables", feature = "polynomial_bias"))]
+#[test]
+fn estimator_test_exact_counter() {
+    let mut exact = ExactCounter::new_deterministic(1);
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1);
+    assert!(exact.is_empty());
+    for x in (0..20_000u32).chain(10_000..30_000) {
+        exact.insert(&x);
+        hll.insert(&x);
+    }
+    assert_eq!(exact.len(), 30_000);
+    assert!(!exact.insert(&0u32));
+    assert!((hll.len() - exact.estimate()).abs() / exact.estimate() < 0.05);
+
+    let other = ExactCounter::new_deterministic(2);
+    assert_eq!(exact.merge(&other), Err(Error::IncompatibleSeed));
+    exact.clear();
+    assert!(exact.is_empty());
+}
@@ -0,0 +1,193 @@
+//! A C interface, declared in `include/hyperloglog.h`.
+//!
+//! Counters are opaque pointers created by `hll_new()` or
+//! `hll_deserialize()`, and must be released with `hll_free()`. Functions
+//! returning an `int` return `0` on success and `-1` on error.
+//!
+//! The crate is only built as a Rust library by default. Build a dynamic
+//! library exporting this interface with:
+//!
+//! ```text
+//! cargo rustc --release --lib --features ffi --crate-type cdylib
+//! ```
+
+use std::os::raw::c_int;
+use std::ptr;
+use std::slice;
+
+use crate::{HashConfig, HyperLogLog, HyperLogLogBuilder};
+
+/// Create a new counter with the given error rate, and a SipHash-1-3 seed
+/// made of two 64-bit halves.
+///
+/// Return a null pointer if the error rate is out of range.
+#[no_mangle]
+pub extern "C" fn hll_new(error_rate: f64, seed_lo: u64, seed_hi: u64) -> *mut HyperLogLog {
+    let seed = u128::from(seed_hi) << 64 | u128::from(seed_lo);
+    match HyperLogLogBuilder::new()
+        .error_rate(error_rate)
+        .hasher(HashConfig::SipHash13 { seed })
+        .build()
+    {
+        Ok(hll) => Box::into_raw(Box::new(hll)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Release a counter. Does nothing if `hll` is null.
+///
+/// # Safety
+///
+/// `hll` must be null or a counter returned by this interface that was not
+/// released yet.
+#[no_mangle]
+pub unsafe extern "C" fn hll_free(hll: *mut HyperLogLog) {
+    if !hll.is_null() {
+        drop(Box::from_raw(hll));
+    }
+}
+
+/// Insert `len` bytes into the counter. Return `1` if a register was
+/// updated, `0` otherwise.
+///
+/// # Safety
+///
+/// `hll` must be a valid counter, and `data` must point to `len` readable
+/// bytes, or be null if `len` is `0`.
+#[no_mangle]
+pub unsafe extern "C" fn hll_insert(hll: *mut HyperLogLog, data: *const u8, len: usize) -> c_int {
+    let data = if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    };
+    (*hll).insert_bytes(data) as c_int
+}
+
+/// Insert a 64-bit hash into the counter. Return `1` if a register was
+/// updated, `0` otherwise.
+///
+/// # Safety
+///
+/// `hll` must be a valid counter.
+#[no_mangle]
+pub unsafe extern "C" fn hll_insert_hash(hll: *mut HyperLogLog, hash: u64) -> c_int {
+    (*hll).insert_by_hash_value(hash) as c_int
+}
+
+/// Merge `src` into `dst`. Fail if the counters are not compatible.
+///
+/// # Safety
+///
+/// `dst` and `src` must be valid counters.
+#[no_mangle]
+pub unsafe extern "C" fn hll_merge(dst: *mut HyperLogLog, src: *const HyperLogLog) -> c_int {
+    match (*dst).try_merge(&*src) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Return the estimated number of distinct values of the counter.
+///
+/// # Safety
+///
+/// `hll` must be a valid counter.
+#[no_mangle]
+pub unsafe extern "C" fn hll_estimate(hll: *const HyperLogLog) -> f64 {
+    (*hll).len()
+}
+
+/// Wipe the counter.
+///
+/// # Safety
+///
+/// `hll` must be a valid counter.
+#[no_mangle]
+pub unsafe extern "C" fn hll_clear(hll: *mut HyperLogLog) {
+    (*hll).clear();
+}
+
+/// Return the size of the serialized counter, in bytes.
+///
+/// # Safety
+///
+/// `hll` must be a valid counter.
+#[no_mangle]
+pub unsafe extern "C" fn hll_serialized_size(hll: *const HyperLogLog) -> usize {
+    HyperLogLog::encoded_len((*hll).precision())
+}
+
+/// Serialize the counter into `out`, using the format of
+/// `HyperLogLog::to_bytes()`. Fail if `out_len` is smaller than
+/// `hll_serialized_size()`.
+///
+/// # Safety
+///
+/// `hll` must be a valid counter, and `out` must point to `out_len`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hll_serialize(
+    hll: *const HyperLogLog,
+    out: *mut u8,
+    out_len: usize,
+) -> c_int {
+    let bytes = (*hll).to_bytes();
+    if out_len < bytes.len() {
+        return -1;
+    }
+    ptr::copy_nonoverlapping(bytes.as_ptr(), out, bytes.len());
+    0
+}
+
+/// Create a counter from `len` bytes serialized by `hll_serialize()`.
+///
+/// Return a null pointer if the data is not a valid counter.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hll_deserialize(data: *const u8, len: usize) -> *mut HyperLogLog {
+    if data.is_null() {
+        return ptr::null_mut();
+    }
+    match HyperLogLog::from_bytes(slice::from_raw_parts(data, len)) {
+        Ok(hll) => Box::into_raw(Box::new(hll)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+#[test]
+fn ffi_test_roundtrip() {
+    unsafe {
+        assert!(hll_new(2.0, 0, 0).is_null());
+        let a = hll_new(0.001, 1, 0);
+        let b = hll_new(0.001, 1, 0);
+        for i in 0..10_000u32 {
+            let bytes = i.to_le_bytes();
+            hll_insert(a, bytes.as_ptr(), bytes.len());
+            let x = (u64::from(i) ^ 0xdead).wrapping_mul(0xbf58476d1ce4e5b9);
+            hll_insert_hash(b, x ^ (x >> 31));
+        }
+        assert_eq!(hll_insert(a, ptr::null(), 0), 1);
+        assert_eq!(hll_merge(a, b), 0);
+        assert!((hll_estimate(a) - 20_001.0).abs() < 1_000.0);
+
+        let mut buf = vec![0u8; hll_serialized_size(a)];
+        assert_eq!(hll_serialize(a, buf.as_mut_ptr(), buf.len() - 1), -1);
+        assert_eq!(hll_serialize(a, buf.as_mut_ptr(), buf.len()), 0);
+        let c = hll_deserialize(buf.as_ptr(), buf.len());
+        assert_eq!(*c, *a);
+        assert!(hll_deserialize(buf.as_ptr(), 3).is_null());
+
+        let other = hll_new(0.001, 2, 0);
+        assert_eq!(hll_merge(a, other), -1);
+        hll_clear(a);
+        assert_eq!(hll_estimate(a), 0.0);
+        for hll in [a, b, c, other] {
+            hll_free(hll);
+        }
+        hll_free(ptr::null_mut());
+    }
+}
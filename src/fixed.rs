@@ -0,0 +1,190 @@
+//! `HyperLogLog` counters whose registers are stored inline, for
+//! allocation-free use.
+//!
+//! The registers are kept in a `[u8; N]` [`RegisterStore`](crate::RegisterStore),
+//! so these counters share the insertion, estimation and merging code of
+//! [`HyperLogLog`].
+//!
+//! The crate still depends on `std`, including for the floating-point
+//! functions used by the estimator, so these counters can't be used on
+//! `no_std` targets such as microcontrollers yet.
+
+use std::hash::Hash;
+
+use crate::{Changes, Error, HashConfig, HyperLogLog, MAX_PRECISION, MIN_PRECISION};
+
+/// A HyperLogLog counter with `N` registers stored in a `[u8; N]`, so that
+/// it can live on the stack or in a static, without heap allocations.
+///
+/// `N` must be a power of two between `2^4` and `2^26`; other values are
+/// rejected at compile time. The precision is the base-2 logarithm of `N`.
+///
+/// Inserting values, estimating and merging never allocate, except when
+/// hashing values longer than 224 bytes with XXH3 or MurmurHash3.
+///
+/// Counters can be created in const contexts, and declared as statics:
+///
+/// ```
+/// use hyperloglog::HyperLogLogFixed;
+/// use std::sync::Mutex;
+///
+/// static COUNTER: Mutex<HyperLogLogFixed<16384>> =
+///     Mutex::new(HyperLogLogFixed::new_deterministic(42));
+///
+/// COUNTER.lock().unwrap().insert(&"sensor-1");
+/// assert!(!COUNTER.lock().unwrap().is_empty());
+/// ```
+#[derive(Clone, Debug)]
+pub struct HyperLogLogFixed<const N: usize>(HyperLogLog<[u8; N]>);
+
+impl<const N: usize> HyperLogLogFixed<N> {
+    const P: u8 = {
+        assert!(
+            N.is_power_of_two() && N >= 1 << MIN_PRECISION && N <= 1 << MAX_PRECISION,
+            "The number of registers must be a power of two between 2^4 and 2^26"
+        );
+        N.trailing_zeros() as u8
+    };
+
+    /// Create a new counter with the given seed.
+    #[must_use]
+    pub const fn new_deterministic(seed: u128) -> Self {
+        Self::with_hash_config(HashConfig::with_seed(seed))
+    }
+
+    /// Create a new counter using the given hash function.
+    #[must_use]
+    pub const fn with_hash_config(hash: HashConfig) -> Self {
+        HyperLogLogFixed(HyperLogLog {
+            alpha: HyperLogLog::get_alpha(Self::P),
+            p: Self::P,
+            m: N,
+            M: [0; N],
+            hash,
+            changes: Changes::new(),
+        })
+    }
+
+    /// Create a new counter with a random seed. Requires the `random`
+    /// feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new() -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(seed)
+    }
+
+    /// Return the precision of the counter.
+    #[must_use]
+    pub fn precision(&self) -> u8 {
+        Self::P
+    }
+
+    /// Return the hash function used by the counter.
+    #[must_use]
+    pub fn hash_config(&self) -> HashConfig {
+        self.0.hash_config()
+    }
+
+    /// Return the content of the registers.
+    #[must_use]
+    pub fn registers(&self) -> &[u8; N] {
+        self.0.store()
+    }
+
+    /// Insert a new value into the counter.
+    ///
+    /// Return `true` if a register was updated.
+    pub fn insert<V: Hash>(&mut self, value: &V) -> bool {
+        self.0.insert(value)
+    }
+
+    /// Insert a new u64 value into the counter.
+    ///
+    /// Return `true` if a register was updated.
+    pub fn insert_by_hash_value(&mut self, x: u64) -> bool {
+        self.0.insert_by_hash_value(x)
+    }
+
+    /// Return the cardinality of the counter.
+    #[must_use]
+    pub fn len(&self) -> f64 {
+        self.0.len()
+    }
+
+    /// Return `true` if the counter is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Merge another counter into the current one.
+    ///
+    /// Return an error if the counters don't use the same hash function.
+    pub fn merge(&mut self, src: &Self) -> Result<(), Error> {
+        self.0.try_merge(&src.0)
+    }
+
+    /// Return a `HyperLogLog` counter with the same registers. This
+    /// allocates.
+    #[must_use]
+    pub fn to_hyperloglog(&self) -> HyperLogLog {
+        self.0.to_hyperloglog()
+    }
+
+    /// Wipe the counter.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+impl<const N: usize> PartialEq for HyperLogLogFixed<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash_config() == other.hash_config() && self.registers() == other.registers()
+    }
+}
+
+impl<const N: usize> Eq for HyperLogLogFixed<N> {}
+
+#[cfg(feature = "random")]
+impl<const N: usize> Default for HyperLogLogFixed<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn fixed_test_hyperloglog() {
+    let mut fixed = HyperLogLogFixed::<4096>::new_deterministic(1);
+    let mut hll = HyperLogLog::from_parts(12, fixed.hash_config(), vec![0; 4096]);
+    assert!(fixed.is_empty());
+    assert_eq!(fixed.precision(), 12);
+    for i in 0..50_000u32 {
+        assert_eq!(fixed.insert(&i), hll.insert(&i));
+    }
+    assert_eq!(fixed.to_hyperloglog(), hll);
+    assert_eq!(fixed.len(), hll.len());
+
+    let mut other = HyperLogLogFixed::<4096>::new_deterministic(1);
+    for i in 50_000..60_000u32 {
+        other.insert(&i);
+    }
+    fixed.merge(&other).unwrap();
+    hll.insert_all(50_000..60_000u32);
+    assert_eq!(fixed.to_hyperloglog(), hll);
+
+    let incompatible = HyperLogLogFixed::<4096>::new_deterministic(2);
+    assert_eq!(fixed.merge(&incompatible), Err(Error::IncompatibleSeed));
+    fixed.clear();
+    assert!(fixed.is_empty());
+}
+
+#[test]
+fn fixed_test_const() {
+    static EMPTY: HyperLogLogFixed<16> = HyperLogLogFixed::new_deterministic(1);
+    let mut hll = EMPTY.clone();
+    assert!(hll.is_empty());
+    hll.insert(&1);
+    assert_eq!(hll.hash_config(), HashConfig::with_seed(1));
+    assert!(EMPTY.is_empty() && !hll.is_empty());
+}
@@ -0,0 +1,148 @@
+//! Generators of valid `HyperLogLog` counters, for the crate's own property
+//! tests.
+
+use crate::{HashConfig, HyperLogLog, MIN_PRECISION};
+
+/// The largest precision of generated counters, to keep them small enough
+/// for fuzzers.
+const MAX_GENERATED_PRECISION: u8 = 16;
+
+impl HyperLogLog {
+    /// Build a valid counter from arbitrary bytes, such as the input of a
+    /// fuzzer.
+    ///
+    /// The first byte selects the precision, between 4 and 16, and the hash
+    /// function, the next bytes the seed, and the remaining bytes the
+    /// registers, reduced to their valid range. Missing bytes are read as
+    /// zeros, so every input produces a counter, and the same input always
+    /// produces the same counter.
+    #[must_use]
+    pub(crate) fn from_arbitrary_bytes(data: &[u8]) -> Self {
+        let mut bytes = data.iter().copied().chain(std::iter::repeat(0));
+        let mut next = || bytes.next().unwrap();
+        let first = next();
+        let p = MIN_PRECISION + first % (MAX_GENERATED_PRECISION - MIN_PRECISION + 1);
+        let mut seed = [0u8; 16];
+        seed.iter_mut().for_each(|x| *x = next());
+        let seed = u128::from_le_bytes(seed);
+        let hash = match first / 16 % 3 {
+            0 => HashConfig::SipHash13 { seed },
+            #[cfg(feature = "xxh3")]
+            1 => HashConfig::Xxh3 { seed: seed as u64 },
+            _ => HashConfig::Murmur3 { seed: seed as u32 },
+        };
+        let max_register = 64 - p + 1;
+        let registers = (0..1usize << p)
+            .map(|_| next() % (max_register + 1))
+            .collect();
+        Self::from_parts(p, hash, registers)
+    }
+}
+
+/// A deterministic generator of counters and values, for property tests.
+///
+/// It produces counters, pairs of compatible counters that can be merged,
+/// and sequences of values to insert into them. The same seed always
+/// produces the same sequence of outputs, so failing cases can be
+/// reproduced from the seed alone.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    state: u64,
+}
+
+impl Generator {
+    /// Create a new generator from a seed.
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Generator { state: seed }
+    }
+
+    // SplitMix64
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Return a sequence of up to `max_len` values to insert into a counter.
+    /// Values are drawn from a range twice as large as the sequence, so that
+    /// sequences include duplicates.
+    pub fn insert_sequence(&mut self, max_len: usize) -> Vec<u64> {
+        let len = (self.next_u64() % (max_len as u64 + 1)) as usize;
+        let range = (len as u64 * 2).max(1);
+        (0..len).map(|_| self.next_u64() % range).collect()
+    }
+
+    /// Return a counter with random parameters and registers, built with
+    /// `HyperLogLog::from_arbitrary_bytes()`.
+    pub fn hyperloglog(&mut self) -> HyperLogLog {
+        let first = self.next_u64() as u8;
+        let p = MIN_PRECISION + first % (MAX_GENERATED_PRECISION - MIN_PRECISION + 1);
+        let mut data = vec![first];
+        while data.len() < 17 + (1 << p) {
+            data.extend_from_slice(&self.next_u64().to_le_bytes());
+        }
+        HyperLogLog::from_arbitrary_bytes(&data)
+    }
+
+    /// Return two counters with the same precision and hash function, each
+    /// filled with a sequence of up to `max_len` values.
+    pub fn compatible_pair(&mut self, max_len: usize) -> (HyperLogLog, HyperLogLog) {
+        let template = self.hyperloglog();
+        let mut pair = (
+            HyperLogLog::new_from_template(&template),
+            HyperLogLog::new_from_template(&template),
+        );
+        pair.0.insert_all(self.insert_sequence(max_len));
+        pair.1.insert_all(self.insert_sequence(max_len));
+        pair
+    }
+}
+
+#[test]
+fn generate_test_from_arbitrary_bytes() {
+    let empty = HyperLogLog::from_arbitrary_bytes(&[]);
+    assert_eq!(empty.precision(), MIN_PRECISION);
+    assert_eq!(empty.len(), 0.0);
+
+    let mut data = vec![0u8; 1 << 10];
+    for (i, x) in data.iter_mut().enumerate() {
+        *x = (i * 7919 % 251) as u8;
+    }
+    for first in 0..=255u8 {
+        data[0] = first;
+        let hll = HyperLogLog::from_arbitrary_bytes(&data);
+        let p = hll.precision();
+        assert!((MIN_PRECISION..=MAX_GENERATED_PRECISION).contains(&p));
+        assert!(hll.registers().iter().all(|&x| x <= 64 - p + 1));
+        assert_eq!(HyperLogLog::from_bytes(&hll.to_bytes()).unwrap(), hll);
+        assert_eq!(HyperLogLog::from_arbitrary_bytes(&data), hll);
+    }
+}
+
+#[test]
+fn generate_test_compatible_pairs() {
+    let mut generator = Generator::new(1);
+    for _ in 0..50 {
+        let (a, b) = generator.compatible_pair(1_000);
+        assert_eq!(a.precision(), b.precision());
+        assert_eq!(a.hash_config(), b.hash_config());
+        let mut ab = a.clone();
+        ab.merge(&b);
+        let mut ba = b.clone();
+        ba.merge(&a);
+        assert_eq!(ab, ba);
+        let mut abb = ab.clone();
+        abb.merge(&b);
+        assert_eq!(abb, ab);
+    }
+    let sequence = Generator::new(2).insert_sequence(100);
+    assert!(sequence.len() <= 100);
+    assert_eq!(Generator::new(2).insert_sequence(100), sequence);
+    assert_eq!(
+        Generator::new(3).hyperloglog(),
+        Generator::new(3).hyperloglog()
+    );
+}
@@ -0,0 +1,358 @@
+// Selection of the hash function used to map values to registers.
+
+use std::convert::TryInto;
+
+pub(crate) use self::one_shot::OneShotHasher;
+use crate::HashConfig;
+use siphasher::sip::SipHasher13;
+use siphasher::sip128::{Hasher128, SipHasher13 as SipHasher13_128};
+use std::hash::{Hash, Hasher};
+
+impl HashConfig {
+    // Overwrite the configuration, including the padding bytes, with writes
+    // the compiler can't optimize away.
+    #[cfg(feature = "zeroize")]
+    pub(crate) fn zeroize(&mut self) {
+        let ptr: *mut HashConfig = self;
+        unsafe {
+            let bytes = ptr.cast::<u8>();
+            for i in 0..size_of::<HashConfig>() {
+                std::ptr::write_volatile(bytes.add(i), 0);
+            }
+            std::ptr::write_volatile(ptr, HashConfig::SipHash13 { seed: 0 });
+        }
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+// Implement `Drop` for types storing the hash configuration in a `hash`
+// field, to wipe the seed with the `zeroize` feature.
+macro_rules! zeroize_on_drop {
+    ($($t:ty),* $(,)?) => {
+        $(
+            #[cfg(feature = "zeroize")]
+            impl Drop for $t {
+                fn drop(&mut self) {
+                    self.hash.zeroize();
+                }
+            }
+        )*
+    };
+}
+pub(crate) use zeroize_on_drop;
+
+/// The hasher for a `HashConfig`, selected at runtime so that counters using
+/// different hash functions can coexist in the same program.
+///
+/// Integers are hashed as little-endian bytes, and `usize` as a 64-bit
+/// integer, so that the same values map to the same registers on every
+/// architecture.
+pub(crate) enum ValueHasher {
+    SipHash13(SipHasher13),
+    OneShot(OneShotHasher),
+}
+
+impl Hasher for ValueHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        match self {
+            ValueHasher::SipHash13(h) => h.write(bytes),
+            ValueHasher::OneShot(h) => h.write(bytes),
+        }
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        match self {
+            ValueHasher::SipHash13(h) => h.finish(),
+            ValueHasher::OneShot(h) => h.finish(),
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn new_hasher(config: HashConfig) -> ValueHasher {
+    match config {
+        HashConfig::SipHash13 { seed } => {
+            ValueHasher::SipHash13(SipHasher13::new_with_keys((seed >> 64) as u64, seed as u64))
+        }
+        #[cfg(feature = "xxh3")]
+        HashConfig::Xxh3 { seed } => ValueHasher::OneShot(OneShotHasher::new(
+            seed,
+            xxhash_rust::xxh3::xxh3_64_with_seed,
+        )),
+        #[cfg(not(feature = "xxh3"))]
+        HashConfig::Xxh3 { .. } => panic!("XXH3 requires the `xxh3` feature"),
+        HashConfig::Murmur3 { seed } => {
+            ValueHasher::OneShot(OneShotHasher::new(seed as u64, |data, seed| {
+                murmur3_x64_128(data, seed as u32).0
+            }))
+        }
+    }
+}
+
+/// A hasher computing MurmurHash3_x86_32, the 32-bit hash function of
+/// stream-lib and other implementations of the original HyperLogLog.
+#[inline]
+pub(crate) fn new_hasher32(seed: u32) -> ValueHasher {
+    ValueHasher::OneShot(OneShotHasher::new(seed as u64, |data, seed| {
+        murmur3_x86_32(data, seed as u32) as u64
+    }))
+}
+
+/// Hash a value to 128 bits with the function selected by `config`:
+/// SipHash-1-3-128, XXH3-128, or MurmurHash3_x64_128 with `h1` in the low
+/// 64 bits.
+pub(crate) fn hash128<V: Hash + ?Sized>(config: HashConfig, value: &V) -> u128 {
+    let hash: fn(&[u8], u64) -> u128 = match config {
+        HashConfig::SipHash13 { seed } => {
+            let mut sip = SipHasher13_128::new_with_keys((seed >> 64) as u64, seed as u64);
+            value.hash(&mut sip);
+            return sip.finish128().as_u128();
+        }
+        #[cfg(feature = "xxh3")]
+        HashConfig::Xxh3 { .. } => xxhash_rust::xxh3::xxh3_128_with_seed,
+        #[cfg(not(feature = "xxh3"))]
+        HashConfig::Xxh3 { .. } => panic!("XXH3 requires the `xxh3` feature"),
+        HashConfig::Murmur3 { .. } => |data, seed| {
+            let (h1, h2) = murmur3_x64_128(data, seed as u32);
+            (h2 as u128) << 64 | h1 as u128
+        },
+    };
+    let mut hasher = new_hasher(config);
+    value.hash(&mut hasher);
+    match hasher {
+        ValueHasher::OneShot(h) => h.finish_with(hash),
+        ValueHasher::SipHash13(_) => unreachable!(),
+    }
+}
+
+/// MurmurHash3_x64_128, returning `(h1, h2)`. Implementations in other
+/// languages commonly use `h1` as the 64-bit hash.
+pub(crate) fn murmur3_x64_128(data: &[u8], seed: u32) -> (u64, u64) {
+    const C1: u64 = 0x87c37b91114253d5;
+    const C2: u64 = 0x4cf5ad432745937f;
+
+    fn fmix64(mut k: u64) -> u64 {
+        k ^= k >> 33;
+        k = k.wrapping_mul(0xff51afd7ed558ccd);
+        k ^= k >> 33;
+        k = k.wrapping_mul(0xc4ceb9fe1a85ec53);
+        k ^ (k >> 33)
+    }
+
+    let (mut h1, mut h2) = (seed as u64, seed as u64);
+    let blocks = data.chunks_exact(16);
+    let tail = blocks.remainder();
+    for block in blocks {
+        let k1 = u64::from_le_bytes(block[..8].try_into().unwrap());
+        let k2 = u64::from_le_bytes(block[8..].try_into().unwrap());
+        h1 ^= k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+        h1 = h1
+            .rotate_left(27)
+            .wrapping_add(h2)
+            .wrapping_mul(5)
+            .wrapping_add(0x52dce729);
+        h2 ^= k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+        h2 = h2
+            .rotate_left(31)
+            .wrapping_add(h1)
+            .wrapping_mul(5)
+            .wrapping_add(0x38495ab5);
+    }
+    let (mut k1, mut k2) = (0u64, 0u64);
+    for (i, &b) in tail.iter().enumerate() {
+        if i < 8 {
+            k1 |= (b as u64) << (i * 8);
+        } else {
+            k2 |= (b as u64) << ((i - 8) * 8);
+        }
+    }
+    if tail.len() > 8 {
+        h2 ^= k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+    }
+    if !tail.is_empty() {
+        h1 ^= k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+    }
+    h1 ^= data.len() as u64;
+    h2 ^= data.len() as u64;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = fmix64(h1);
+    h2 = fmix64(h2);
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    (h1, h2)
+}
+
+/// MurmurHash3_x86_32.
+pub(crate) fn murmur3_x86_32(data: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e2d51;
+    const C2: u32 = 0x1b873593;
+
+    let mut h = seed;
+    let blocks = data.chunks_exact(4);
+    let tail = blocks.remainder();
+    for block in blocks {
+        let k = u32::from_le_bytes(block.try_into().unwrap());
+        h ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+    if !tail.is_empty() {
+        let k = tail.iter().rev().fold(0u32, |k, &b| (k << 8) | b as u32);
+        h ^= k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+    h ^= data.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85ebca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2ae35);
+    h ^ (h >> 16)
+}
+
+mod one_shot {
+    use std::hash::Hasher;
+    use std::mem::MaybeUninit;
+    use std::{ptr, slice};
+
+    // Values up to this size are hashed without allocating. It keeps the
+    // hasher small enough to be moved around cheaply next to SipHash.
+    pub(crate) const INLINE_SIZE: usize = 224;
+
+    /// Buffers the hashed data, so that the hash function can be computed in
+    /// one shot. Streaming states are expensive to set up, while one-shot
+    /// functions are fast for short inputs.
+    ///
+    /// The inline buffer is left uninitialized, and only its first `len`
+    /// bytes are ever read.
+    pub(crate) struct OneShotHasher {
+        seed: u64,
+        hash: fn(&[u8], u64) -> u64,
+        len: usize,
+        inline: [MaybeUninit<u8>; INLINE_SIZE],
+        spill: Vec<u8>,
+    }
+
+    impl OneShotHasher {
+        #[inline]
+        pub(crate) fn new(seed: u64, hash: fn(&[u8], u64) -> u64) -> Self {
+            OneShotHasher {
+                seed,
+                hash,
+                len: 0,
+                inline: [const { MaybeUninit::uninit() }; INLINE_SIZE],
+                spill: Vec::new(),
+            }
+        }
+
+        /// Hash the buffered data with another function than the one the
+        /// hasher was created with, such as a wider variant.
+        #[inline]
+        pub(crate) fn finish_with<T>(&self, hash: fn(&[u8], u64) -> T) -> T {
+            if self.spill.is_empty() {
+                hash(self.buffered(), self.seed)
+            } else {
+                hash(&self.spill, self.seed)
+            }
+        }
+
+        #[inline]
+        fn buffered(&self) -> &[u8] {
+            // SAFETY: the first `len` bytes have been written by `write()`.
+            unsafe { slice::from_raw_parts(self.inline.as_ptr().cast(), self.len) }
+        }
+    }
+
+    impl Hasher for OneShotHasher {
+        #[inline]
+        fn write(&mut self, bytes: &[u8]) {
+            if self.spill.is_empty() && self.len + bytes.len() <= INLINE_SIZE {
+                let dst = &mut self.inline[self.len..self.len + bytes.len()];
+                // SAFETY: `dst` and `bytes` have the same length, and
+                // `MaybeUninit<u8>` has the same layout as `u8`.
+                unsafe {
+                    ptr::copy_nonoverlapping(bytes.as_ptr(), dst.as_mut_ptr().cast(), bytes.len())
+                };
+                self.len += bytes.len();
+                return;
+            }
+            if self.spill.is_empty() {
+                self.spill = self.buffered().to_vec();
+            }
+            self.spill.extend_from_slice(bytes);
+        }
+
+        #[inline]
+        fn finish(&self) -> u64 {
+            self.finish_with(self.hash)
+        }
+    }
+
+    #[test]
+    fn one_shot_test_buffering() {
+        let data: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+        let hash = |data: &[u8], seed: u64| super::murmur3_x64_128(data, seed as u32).0;
+        let mut hasher = OneShotHasher::new(42, hash);
+        for chunk in data.chunks(7) {
+            hasher.write(chunk);
+        }
+        assert_eq!(hasher.finish(), hash(&data, 42));
+        let mut hasher = OneShotHasher::new(42, hash);
+        hasher.write(&data[..10]);
+        assert_eq!(hasher.finish(), hash(&data[..10], 42));
+        let mut hasher = OneShotHasher::new(42, hash);
+        hasher.write(&data[..INLINE_SIZE]);
+        assert_eq!(hasher.finish(), hash(&data[..INLINE_SIZE], 42));
+        hasher.write(&data[INLINE_SIZE..INLINE_SIZE + 1]);
+        assert_eq!(hasher.finish(), hash(&data[..INLINE_SIZE + 1], 42));
+    }
+}
+
+#[test]
+fn hash_test_murmur3() {
+    assert_eq!(murmur3_x64_128(b"", 0), (0, 0));
+    assert_eq!(
+        murmur3_x64_128(b"hello", 0),
+        (0xcbd8a7b341bd9b02, 0x5b1e906a48ae1d19)
+    );
+    assert_eq!(
+        murmur3_x64_128(b"The quick brown fox jumps over the lazy dog", 0),
+        (0xe34bbc7bbc071b6c, 0x7a433ca9c49a9347)
+    );
+}
+
+#[test]
+fn hash_test_murmur3_x86_32() {
+    assert_eq!(murmur3_x86_32(b"", 0), 0);
+    assert_eq!(murmur3_x86_32(b"", 1), 0x514e28b7);
+    assert_eq!(murmur3_x86_32(b"hello", 0), 0x248bfa47);
+    assert_eq!(
+        murmur3_x86_32(b"The quick brown fox jumps over the lazy dog", 0),
+        0x2e4ff723
+    );
+}
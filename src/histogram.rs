@@ -0,0 +1,242 @@
+//! A `HyperLogLog` exponential histogram for arbitrary window queries.
+
+use std::collections::VecDeque;
+use std::hash::Hash;
+
+use crate::HyperLogLog;
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Bucket {
+    // First and last time slots covered by the bucket.
+    start: u64,
+    end: u64,
+    level: u32,
+    hll: HyperLogLog,
+}
+
+/// A sequence of `HyperLogLog` counters covering geometrically growing time
+/// ranges, estimating the number of distinct values over any trailing
+/// window up to a maximum set at creation.
+///
+/// Time is divided into slots of `resolution` units, and each slot starts
+/// as its own bucket. As in the DGIM algorithm, when more than `k` buckets
+/// have the same level, the two oldest are merged into a bucket of the next
+/// level, which covers twice as many slots. Memory is logarithmic in the
+/// number of slots in the window.
+///
+/// A query merges the buckets that overlap the window. Only the oldest of
+/// them can extend past the window, by at most about `1/(k - 1)` of the
+/// window length, which bounds the overestimation.
+///
+/// Timestamps are arbitrary `u64` units, such as seconds, and are expected
+/// to be non-decreasing. A timestamp older than the latest one is treated
+/// as the latest one.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExpHistogramHyperLogLog {
+    template: HyperLogLog,
+    resolution: u64,
+    window: u64,
+    k: usize,
+    // Oldest bucket first.
+    buckets: VecDeque<Bucket>,
+}
+
+impl ExpHistogramHyperLogLog {
+    /// Create a new `ExpHistogramHyperLogLog` counter with the given error
+    /// rate, maximum window, resolution and seed, keeping up to 8 buckets
+    /// per level.
+    #[must_use]
+    pub fn new_deterministic(error_rate: f64, window: u64, resolution: u64, seed: u128) -> Self {
+        Self::with_template(
+            &HyperLogLog::new_deterministic(error_rate, seed),
+            window,
+            resolution,
+            8,
+        )
+    }
+
+    /// Create a new `ExpHistogramHyperLogLog` counter with the given error
+    /// rate, maximum window, resolution and a random seed. Requires the
+    /// `random` feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(error_rate: f64, window: u64, resolution: u64) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(error_rate, window, resolution, seed)
+    }
+
+    /// Create a new, empty `ExpHistogramHyperLogLog` counter with the given
+    /// maximum window, resolution and number of buckets per level, whose
+    /// buckets have the same parameters as `template`.
+    ///
+    /// More buckets per level reduce the overestimation, at the cost of
+    /// memory.
+    #[must_use]
+    pub fn with_template(template: &HyperLogLog, window: u64, resolution: u64, k: usize) -> Self {
+        assert!(resolution > 0 && k > 0);
+        ExpHistogramHyperLogLog {
+            template: HyperLogLog::new_from_template(template),
+            resolution,
+            window,
+            k,
+            buckets: VecDeque::new(),
+        }
+    }
+
+    /// Return the number of buckets currently in use.
+    #[must_use]
+    pub fn num_buckets(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Insert a new value, seen at `timestamp`.
+    pub fn insert<V: Hash>(&mut self, value: &V, timestamp: u64) {
+        self.bucket_mut(timestamp).insert(value);
+    }
+
+    /// Insert a new u64 value, seen at `timestamp`.
+    pub fn insert_by_hash_value(&mut self, x: u64, timestamp: u64) {
+        self.bucket_mut(timestamp).insert_by_hash_value(x);
+    }
+
+    /// Return a regular `HyperLogLog` counter with the values inserted
+    /// during the `window` time units ending at `now`, rounded to the
+    /// buckets overlapping the window. `window` is capped to the maximum
+    /// window.
+    #[must_use]
+    pub fn to_hyperloglog(&self, now: u64, window: u64) -> HyperLogLog {
+        let window = window.min(self.window);
+        let mut hll = HyperLogLog::new_from_template(&self.template);
+        let srcs: Vec<_> = self
+            .buckets
+            .iter()
+            .filter(|bucket| {
+                bucket.start * self.resolution <= now
+                    && Self::in_window(self.slot_last(bucket.end), now, window)
+            })
+            .map(|bucket| &bucket.hll)
+            .collect();
+        hll.merge_many(&srcs);
+        hll
+    }
+
+    /// Return the number of distinct values inserted during the `window`
+    /// time units ending at `now`. `window` is capped to the maximum window.
+    #[must_use]
+    pub fn len_window(&self, now: u64, window: u64) -> f64 {
+        self.to_hyperloglog(now, window).len()
+    }
+
+    /// Return `true` if the counter is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /// Wipe the counter.
+    pub fn clear(&mut self) {
+        self.buckets.clear();
+    }
+
+    // Return the last timestamp of the slot.
+    fn slot_last(&self, slot: u64) -> u64 {
+        (slot + 1).saturating_mul(self.resolution) - 1
+    }
+
+    fn in_window(t: u64, now: u64, window: u64) -> bool {
+        t.saturating_add(window) > now
+    }
+
+    fn bucket_mut(&mut self, timestamp: u64) -> &mut HyperLogLog {
+        let slot = timestamp / self.resolution;
+        let newest = self.buckets.back().map(|bucket| bucket.end);
+        if newest.is_none_or(|newest| newest < slot) {
+            self.buckets.push_back(Bucket {
+                start: slot,
+                end: slot,
+                level: 0,
+                hll: HyperLogLog::new_from_template(&self.template),
+            });
+            self.compact();
+            self.expire(slot);
+        }
+        &mut self.buckets.back_mut().unwrap().hll
+    }
+
+    // Merge the two oldest buckets of each level that has too many of them.
+    fn compact(&mut self) {
+        let mut level = 0;
+        loop {
+            let same_level: Vec<usize> = (0..self.buckets.len())
+                .filter(|&i| self.buckets[i].level == level)
+                .collect();
+            if same_level.len() <= self.k {
+                break;
+            }
+            let (older, newer) = (same_level[0], same_level[1]);
+            let newer = self.buckets.remove(newer).unwrap();
+            let bucket = &mut self.buckets[older];
+            bucket.hll.merge(&newer.hll);
+            bucket.end = newer.end;
+            bucket.level += 1;
+            level += 1;
+        }
+    }
+
+    fn expire(&mut self, slot: u64) {
+        let now = slot * self.resolution;
+        while self
+            .buckets
+            .front()
+            .is_some_and(|bucket| !Self::in_window(self.slot_last(bucket.end), now, self.window))
+        {
+            self.buckets.pop_front();
+        }
+    }
+}
+
+#[test]
+fn histogram_test_windows() {
+    let mut hll = ExpHistogramHyperLogLog::new_deterministic(0.001, 10_000, 1, 1);
+    assert!(hll.is_empty());
+    for t in 0..20_000u64 {
+        for i in 0..5u64 {
+            hll.insert(&(t * 5 + i), t);
+        }
+    }
+    assert!(hll.num_buckets() <= 8 * 11);
+    let now = 19_999;
+    for &window in &[1u64, 10, 100, 1000, 10_000] {
+        let expected = (window * 5) as f64;
+        let estimate = hll.len_window(now, window);
+        assert!(estimate >= expected * 0.95);
+        assert!(estimate <= expected * 1.25);
+    }
+    assert!((hll.len_window(now, 100_000) - hll.len_window(now, 10_000)).abs() < f64::EPSILON);
+
+    let mut expected = HyperLogLog::new_deterministic(0.001, 1);
+    expected.insert_all(now * 5..now * 5 + 5);
+    assert_eq!(hll.to_hyperloglog(now, 1), expected);
+    hll.clear();
+    assert!(hll.is_empty());
+}
+
+#[test]
+fn histogram_test_levels() {
+    let template = HyperLogLog::new_deterministic(0.04, 1);
+    let mut hll = ExpHistogramHyperLogLog::with_template(&template, 1 << 20, 1, 2);
+    for t in 0..1000u64 {
+        hll.insert(&t, t);
+    }
+    let levels: Vec<u32> = hll.buckets.iter().map(|bucket| bucket.level).collect();
+    assert!(levels.windows(2).all(|w| w[0] >= w[1]));
+    assert!(hll
+        .buckets
+        .iter()
+        .zip(hll.buckets.iter().skip(1))
+        .all(|(a, b)| a.end + 1 == b.start));
+    assert_eq!(hll.buckets[0].start, 0);
+    assert!(hll.num_buckets() <= 2 * 10 + 1);
+}
@@ -0,0 +1,253 @@
+//! HyperMinHash: `HyperLogLog` registers extended with MinHash bits.
+
+use std::hash::{Hash, Hasher};
+
+use crate::{Error, HashConfig, HyperLogLog};
+
+const MANTISSA_BITS: u32 = 10;
+
+/// A HyperMinHash sketch, estimating cardinalities like a `HyperLogLog`
+/// counter, as well as Jaccard indices and intersection cardinalities
+/// between sets.
+///
+/// Each register stores the usual `HyperLogLog` value, followed by
+/// `MANTISSA_BITS` bits of another hash of the value having the largest
+/// `HyperLogLog` value, keeping the smallest one on ties. Two sets sharing a
+/// register value most likely share the value that produced it, which gives
+/// accurate Jaccard indices even for small overlaps, unlike
+/// inclusion-exclusion on `HyperLogLog` counters.
+///
+/// See "HyperMinHash: MinHash in LogLog space", Yu and Weber, 2017.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HyperMinHash {
+    p: u8,
+    hash: HashConfig,
+    // The `HyperLogLog` value in the high bits, and the complement of the
+    // MinHash bits in the low bits, so that merging takes the maximum.
+    M: Vec<u16>,
+}
+
+crate::hash::zeroize_on_drop!(HyperMinHash);
+
+impl HyperMinHash {
+    /// Create a new `HyperMinHash` sketch with the given error rate and seed.
+    #[must_use]
+    pub fn new_deterministic(error_rate: f64, seed: u128) -> Self {
+        let p = HyperLogLog::precision_for_error_rate(error_rate);
+        HyperMinHash {
+            p,
+            hash: HashConfig::with_seed(seed),
+            M: vec![0; 1usize << p],
+        }
+    }
+
+    /// Create a new `HyperMinHash` sketch with the given error rate and a
+    /// random seed. Requires the `random` feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(error_rate: f64) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(error_rate, seed)
+    }
+
+    /// Create a new, empty `HyperMinHash` sketch with the same parameters as
+    /// an existing one.
+    #[must_use]
+    pub fn new_from_template(hmh: &HyperMinHash) -> Self {
+        HyperMinHash {
+            p: hmh.p,
+            hash: hmh.hash,
+            M: vec![0; hmh.M.len()],
+        }
+    }
+
+    /// Insert a new value into the sketch.
+    pub fn insert<V: Hash>(&mut self, value: &V) {
+        let mut sip = crate::hash::new_hasher(self.hash);
+        value.hash(&mut sip);
+        self.insert_by_hash_value(sip.finish());
+    }
+
+    /// Insert all the values of an iterator into the sketch.
+    pub fn insert_all<I>(&mut self, values: I)
+    where
+        I: IntoIterator,
+        I::Item: Hash,
+    {
+        for value in values {
+            self.insert(&value);
+        }
+    }
+
+    /// Insert a new u64 value into the sketch.
+    pub fn insert_by_hash_value(&mut self, x: u64) {
+        let (j, rho) = HyperLogLog::register_update(x, self.p);
+        let mantissa = mix(x) & ((1 << MANTISSA_BITS) - 1);
+        let value = (rho as u16) << MANTISSA_BITS | !(mantissa as u16) & ((1 << MANTISSA_BITS) - 1);
+        let mjr = &mut self.M[j];
+        if value > *mjr {
+            *mjr = value;
+        }
+    }
+
+    /// Return the cardinality of the sketch.
+    #[must_use]
+    pub fn len(&self) -> f64 {
+        self.to_hyperloglog().len()
+    }
+
+    /// Return `true` if the sketch is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.M.iter().all(|&x| x == 0)
+    }
+
+    /// Return `true` if `other` has the same precision and hash function as
+    /// the current sketch, so that both can be merged and compared.
+    #[must_use]
+    pub fn is_compatible(&self, other: &HyperMinHash) -> bool {
+        self.p == other.p && self.hash == other.hash
+    }
+
+    /// Merge another sketch into the current one.
+    ///
+    /// Panics if the sketches are not compatible.
+    pub fn merge(&mut self, src: &HyperMinHash) {
+        if let Err(e) = self.try_merge(src) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Merge another sketch into the current one, returning an error
+    /// instead of panicking if the sketches are not compatible.
+    pub fn try_merge(&mut self, src: &HyperMinHash) -> Result<(), Error> {
+        if src.p != self.p {
+            return Err(Error::IncompatiblePrecision);
+        }
+        self.hash.check_compatible(src.hash)?;
+        for (mir, &src_mir) in self.M.iter_mut().zip(src.M.iter()) {
+            *mir = (*mir).max(src_mir);
+        }
+        Ok(())
+    }
+
+    /// Return the Jaccard index of the sets represented by the two
+    /// sketches, i.e. the cardinality of their intersection divided by the
+    /// cardinality of their union.
+    ///
+    /// Panics if the sketches are not compatible.
+    #[must_use]
+    pub fn jaccard(&self, other: &HyperMinHash) -> f64 {
+        assert!(self.is_compatible(other));
+        let (mut matches, mut non_empty) = (0usize, 0usize);
+        for (&a, &b) in self.M.iter().zip(other.M.iter()) {
+            if a != 0 || b != 0 {
+                non_empty += 1;
+                matches += (a == b) as usize;
+            }
+        }
+        if non_empty == 0 {
+            return 0.0;
+        }
+        let collisions = self.expected_collisions(self.len(), other.len());
+        ((matches as f64 - collisions) / non_empty as f64).clamp(0.0, 1.0)
+    }
+
+    /// Return the cardinality of the intersection of the sets represented
+    /// by the two sketches.
+    ///
+    /// Panics if the sketches are not compatible.
+    #[must_use]
+    pub fn intersection_len(&self, other: &HyperMinHash) -> f64 {
+        let mut union = self.clone();
+        union.merge(other);
+        self.jaccard(other) * union.len()
+    }
+
+    /// Return a `HyperLogLog` counter with the `HyperLogLog` part of the
+    /// registers.
+    #[must_use]
+    pub fn to_hyperloglog(&self) -> HyperLogLog {
+        let M = self.M.iter().map(|&x| (x >> MANTISSA_BITS) as u8).collect();
+        HyperLogLog::from_parts(self.p, self.hash, M)
+    }
+
+    /// Wipe the sketch.
+    pub fn clear(&mut self) {
+        self.M.iter_mut().for_each(|x| *x = 0);
+    }
+
+    // Return the expected number of registers that are equal by chance in
+    // sketches of independent sets with `n` and `m` values.
+    fn expected_collisions(&self, n: f64, m: f64) -> f64 {
+        let registers = (1usize << self.p) as f64;
+        let max_rho = 64 - self.p as i32 + 1;
+        let slots = (1u32 << MANTISSA_BITS) as f64;
+        // Probability that a register holds at most the `k`-th smallest
+        // value, given the probability `g` that a single value is below it.
+        let cdf = |g: f64, n: f64| (n * (-(1.0 - g) / registers).ln_1p()).exp();
+        let mut collisions = 0.0;
+        let mut below = 0.0;
+        for rho in 1..=max_rho {
+            let p_rho = if rho == max_rho {
+                crate::POW2_NEG[rho as usize - 1]
+            } else {
+                crate::POW2_NEG[rho as usize]
+            };
+            let (mut prev_a, mut prev_b) = (cdf(below, n), cdf(below, m));
+            for k in 1..=slots as u32 {
+                let g = below + p_rho * k as f64 / slots;
+                let (a, b) = (cdf(g, n), cdf(g, m));
+                collisions += (a - prev_a) * (b - prev_b);
+                prev_a = a;
+                prev_b = b;
+            }
+            below += p_rho;
+        }
+        collisions * registers
+    }
+}
+
+// A bijective mixer, to derive bits that are independent from the ones
+// used for the register index and the `HyperLogLog` value.
+fn mix(mut x: u64) -> u64 {
+    x ^= x >> 30;
+    x = x.wrapping_mul(0xbf58476d1ce4e5b9);
+    x ^= x >> 27;
+    x = x.wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+#[test]
+fn hyperminhash_test_cardinality() {
+    let mut hmh = HyperMinHash::new_deterministic(0.001, 1);
+    assert!(hmh.is_empty());
+    hmh.insert_all(0..100_000u32);
+    assert!((hmh.len() - 100_000.0).abs() < 3_000.0);
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1);
+    hll.insert_all(0..100_000u32);
+    assert_eq!(hmh.to_hyperloglog(), hll);
+    hmh.clear();
+    assert!(hmh.is_empty());
+}
+
+#[test]
+fn hyperminhash_test_jaccard() {
+    let mut a = HyperMinHash::new_deterministic(0.001, 1);
+    let mut b = HyperMinHash::new_from_template(&a);
+    a.insert_all(0..1_000_000u32);
+    b.insert_all(990_000..1_990_000u32);
+    let jaccard = 10_000.0 / 1_990_000.0;
+    assert!((a.jaccard(&b) - jaccard).abs() < 0.003);
+    assert!((a.intersection_len(&b) - 10_000.0).abs() < 3_000.0);
+
+    assert!((a.jaccard(&a) - 1.0).abs() < 0.01);
+    let mut c = HyperMinHash::new_from_template(&a);
+    c.insert_all(2_000_000..3_000_000u32);
+    assert!(a.jaccard(&c) < 0.002);
+
+    let mut union = a.clone();
+    union.merge(&b);
+    assert!((union.len() - 1_990_000.0).abs() < 60_000.0);
+}
@@ -0,0 +1,220 @@
+//! K-Minimum Values (bottom-k) sketches.
+
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+
+use crate::{Error, HashConfig};
+
+/// A K-Minimum Values sketch, keeping the `k` smallest distinct hashes of
+/// the inserted values.
+///
+/// Its cardinality estimate has a relative standard error of about
+/// `1/sqrt(k - 2)`, so it needs more memory than a `HyperLogLog` counter for
+/// the same accuracy. But the retained hashes are a uniform sample of the
+/// distinct values, which gives unbiased estimates of intersections and
+/// Jaccard indices, and can be used to sample distinct values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct KmvSketch {
+    k: usize,
+    hash: HashConfig,
+    hashes: BTreeSet<u64>,
+}
+
+crate::hash::zeroize_on_drop!(KmvSketch);
+
+impl KmvSketch {
+    /// Create a new sketch keeping `k` hashes, with the given seed.
+    #[must_use]
+    pub fn new_deterministic(k: usize, seed: u128) -> Self {
+        Self::new_with_hash_config(k, HashConfig::with_seed(seed))
+    }
+
+    /// Create a new sketch keeping `k` hashes, using the given hash
+    /// function.
+    #[must_use]
+    pub fn new_with_hash_config(k: usize, hash: HashConfig) -> Self {
+        assert!(k >= 3);
+        KmvSketch {
+            k,
+            hash,
+            hashes: BTreeSet::new(),
+        }
+    }
+
+    /// Create a new sketch keeping `k` hashes, with a random seed. Requires
+    /// the `random` feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(k: usize) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(k, seed)
+    }
+
+    /// Create a new, empty sketch with the same parameters as an existing
+    /// one.
+    #[must_use]
+    pub fn new_from_template(kmv: &KmvSketch) -> Self {
+        Self::new_with_hash_config(kmv.k, kmv.hash)
+    }
+
+    /// Return the maximum number of hashes kept by the sketch.
+    #[must_use]
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Insert a new value into the sketch.
+    ///
+    /// Return `true` if its hash was added to the sketch.
+    pub fn insert<V: Hash>(&mut self, value: &V) -> bool {
+        let mut sip = crate::hash::new_hasher(self.hash);
+        value.hash(&mut sip);
+        self.insert_by_hash_value(sip.finish())
+    }
+
+    /// Insert all the values of an iterator into the sketch.
+    pub fn insert_all<I>(&mut self, values: I)
+    where
+        I: IntoIterator,
+        I::Item: Hash,
+    {
+        for value in values {
+            self.insert(&value);
+        }
+    }
+
+    /// Insert a new u64 value into the sketch.
+    ///
+    /// Return `true` if it was added to the sketch.
+    pub fn insert_by_hash_value(&mut self, x: u64) -> bool {
+        if self.hashes.len() >= self.k && self.hashes.last().is_some_and(|&max| x >= max) {
+            return false;
+        }
+        if !self.hashes.insert(x) {
+            return false;
+        }
+        if self.hashes.len() > self.k {
+            self.hashes.pop_last();
+        }
+        true
+    }
+
+    /// Return the cardinality of the sketch. It is exact as long as fewer
+    /// than `k` distinct values were inserted.
+    #[must_use]
+    pub fn len(&self) -> f64 {
+        if self.hashes.len() < self.k {
+            return self.hashes.len() as f64;
+        }
+        let kth = *self.hashes.last().unwrap() as f64 + 1.0;
+        (self.k - 1) as f64 / (kth / 2.0f64.powi(64))
+    }
+
+    /// Return `true` if the sketch is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// Return the retained hashes, in increasing order. They are the hashes
+    /// of a uniform sample of the distinct values.
+    pub fn hashes(&self) -> impl Iterator<Item = u64> + '_ {
+        self.hashes.iter().copied()
+    }
+
+    /// Merge another sketch into the current one.
+    ///
+    /// Return an error if the sketches don't have the same `k` and hash
+    /// function.
+    pub fn merge(&mut self, src: &KmvSketch) -> Result<(), Error> {
+        self.check_compatible(src)?;
+        for &x in &src.hashes {
+            self.insert_by_hash_value(x);
+        }
+        Ok(())
+    }
+
+    /// Return the Jaccard index of the sets represented by the two sketches,
+    /// estimated from the `k` smallest hashes of their union.
+    ///
+    /// Return an error if the sketches are not compatible.
+    pub fn jaccard(&self, other: &KmvSketch) -> Result<f64, Error> {
+        let union = self.union(other)?;
+        if union.hashes.is_empty() {
+            return Ok(0.0);
+        }
+        let shared = union
+            .hashes
+            .iter()
+            .filter(|x| self.hashes.contains(x) && other.hashes.contains(x))
+            .count();
+        Ok(shared as f64 / union.hashes.len() as f64)
+    }
+
+    /// Return the cardinality of the intersection of the sets represented by
+    /// the two sketches.
+    ///
+    /// Return an error if the sketches are not compatible.
+    pub fn intersection_len(&self, other: &KmvSketch) -> Result<f64, Error> {
+        Ok(self.jaccard(other)? * self.union(other)?.len())
+    }
+
+    /// Return a sketch of the union of the sets represented by the two
+    /// sketches.
+    ///
+    /// Return an error if the sketches are not compatible.
+    pub fn union(&self, other: &KmvSketch) -> Result<KmvSketch, Error> {
+        let mut union = self.clone();
+        union.merge(other)?;
+        Ok(union)
+    }
+
+    /// Wipe the sketch.
+    pub fn clear(&mut self) {
+        self.hashes.clear();
+    }
+
+    fn check_compatible(&self, other: &KmvSketch) -> Result<(), Error> {
+        if other.k != self.k {
+            return Err(Error::IncompatiblePrecision);
+        }
+        self.hash.check_compatible(other.hash)
+    }
+}
+
+#[test]
+fn kmv_test_cardinality() {
+    let mut kmv = KmvSketch::new_deterministic(1024, 1);
+    assert!(kmv.is_empty());
+    kmv.insert_all(0..500u32);
+    assert!((kmv.len() - 500.0).abs() < f64::EPSILON);
+    assert!(!kmv.insert(&0u32));
+    kmv.insert_all(0..100_000u32);
+    assert_eq!(kmv.hashes().count(), 1024);
+    assert!((kmv.len() - 100_000.0).abs() < 10_000.0);
+    assert!(kmv.hashes().zip(kmv.hashes().skip(1)).all(|(a, b)| a < b));
+    kmv.clear();
+    assert!(kmv.is_empty());
+}
+
+#[test]
+fn kmv_test_set_operations() {
+    let mut a = KmvSketch::new_deterministic(4096, 1);
+    let mut b = KmvSketch::new_from_template(&a);
+    a.insert_all(0..100_000u32);
+    b.insert_all(50_000..150_000u32);
+    assert!((a.jaccard(&b).unwrap() - 1.0 / 3.0).abs() < 0.03);
+    assert!((a.intersection_len(&b).unwrap() - 50_000.0).abs() < 5_000.0);
+    assert!((a.union(&b).unwrap().len() - 150_000.0).abs() < 10_000.0);
+
+    let mut expected = KmvSketch::new_from_template(&a);
+    expected.insert_all(0..150_000u32);
+    a.merge(&b).unwrap();
+    assert_eq!(a, expected);
+
+    let other = KmvSketch::new_deterministic(4096, 2);
+    assert_eq!(a.merge(&other), Err(Error::IncompatibleSeed));
+    let other = KmvSketch::new_deterministic(1024, 1);
+    assert_eq!(a.jaccard(&other), Err(Error::IncompatiblePrecision));
+}
@@ -0,0 +1,81 @@
+//! Loading counters serialized by versions 1.x of this crate.
+
+use std::convert::TryFrom;
+
+use siphasher::sip::SipHasher13;
+
+use crate::{Error, HashConfig, HyperLogLog};
+
+/// A counter serialized with `serde` by versions 1.x of this crate, whose
+/// format differs from the current one.
+///
+/// Data written by these versions can be deserialized into this type, and
+/// converted into a `HyperLogLog` counter with `TryFrom`:
+///
+/// ```ignore
+/// let v1: V1Serialized = bincode::deserialize(&bytes)?;
+/// let hll = HyperLogLog::try_from(v1)?;
+/// ```
+///
+/// Requires the `with_serde` feature.
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct V1Serialized {
+    // Recomputed from the precision.
+    #[serde(rename = "alpha")]
+    _alpha: f64,
+    p: u8,
+    m: usize,
+    M: Vec<u8>,
+    sip: SipHasher13,
+}
+
+impl TryFrom<V1Serialized> for HyperLogLog {
+    type Error = Error;
+
+    /// Convert a counter serialized by versions 1.x, keeping its seed and
+    /// registers.
+    ///
+    /// Return an error if the deserialized parameters are invalid.
+    fn try_from(v1: V1Serialized) -> Result<Self, Error> {
+        if v1.m != v1.M.len() {
+            return Err(Error::InvalidRegisterCount);
+        }
+        let (key0, key1) = v1.sip.keys();
+        let hash = HashConfig::SipHash13 {
+            seed: u128::from(key0) << 64 | u128::from(key1),
+        };
+        HyperLogLog::from_registers_with_hash_config(v1.p, hash, v1.M)
+    }
+}
+
+#[test]
+fn legacy_test_convert() {
+    #[derive(serde::Serialize)]
+    struct Legacy {
+        alpha: f64,
+        p: u8,
+        m: usize,
+        M: Vec<u8>,
+        sip: SipHasher13,
+    }
+
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1 << 100 | 42);
+    hll.insert_all(0..1000u32);
+    let legacy = Legacy {
+        alpha: hll.alpha,
+        p: hll.p,
+        m: hll.m,
+        M: hll.registers().to_vec(),
+        sip: SipHasher13::new_with_keys(1 << 36, 42),
+    };
+    let bytes = bincode::serialize(&legacy).unwrap();
+    let loaded: V1Serialized = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(HyperLogLog::try_from(loaded.clone()), Ok(hll));
+
+    let mut invalid = loaded;
+    invalid.m = 1 << 10;
+    assert_eq!(
+        HyperLogLog::try_from(invalid).unwrap_err(),
+        Error::InvalidRegisterCount
+    );
+}
@@ -0,0 +1,178 @@
+//! Keyed `HyperLogLog` counters, for `COUNT(DISTINCT x) GROUP BY k`
+//! queries.
+
+use std::collections::hash_map::{self, HashMap};
+use std::hash::Hash;
+
+use crate::{Error, HyperLogLog};
+
+/// A map from keys to `HyperLogLog` counters sharing the same parameters.
+///
+/// A counter is created for a key the first time a value is inserted for
+/// it, so the map only uses memory for the keys that were seen. Maps with
+/// the same parameters can be merged key by key.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HllMap<K: Eq + Hash> {
+    template: HyperLogLog,
+    sketches: HashMap<K, HyperLogLog>,
+}
+
+impl<K: Eq + Hash> HllMap<K> {
+    /// Create a new, empty map whose counters have the given error rate
+    /// and seed.
+    #[must_use]
+    pub fn new_deterministic(error_rate: f64, seed: u128) -> Self {
+        Self::with_template(&HyperLogLog::new_deterministic(error_rate, seed))
+    }
+
+    /// Create a new, empty map whose counters have the given error rate and
+    /// a random seed. Requires the `random` feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(error_rate: f64) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(error_rate, seed)
+    }
+
+    /// Create a new, empty map whose counters have the same parameters as
+    /// `template`.
+    #[must_use]
+    pub fn with_template(template: &HyperLogLog) -> Self {
+        HllMap {
+            template: HyperLogLog::new_from_template(template),
+            sketches: HashMap::new(),
+        }
+    }
+
+    /// Insert a new value into the counter of `key`, creating it if needed.
+    pub fn insert<V: Hash>(&mut self, key: K, value: &V) {
+        self.entry(key).insert(value);
+    }
+
+    /// Insert a new u64 value into the counter of `key`, creating it if
+    /// needed.
+    pub fn insert_by_hash_value(&mut self, key: K, x: u64) {
+        self.entry(key).insert_by_hash_value(x);
+    }
+
+    /// Return the number of distinct values inserted for `key`, or `0.0` if
+    /// the key was never seen.
+    #[must_use]
+    pub fn estimate(&self, key: &K) -> f64 {
+        self.sketches.get(key).map_or(0.0, HyperLogLog::len)
+    }
+
+    /// Return the counter of `key`, if any.
+    #[must_use]
+    pub fn get(&self, key: &K) -> Option<&HyperLogLog> {
+        self.sketches.get(key)
+    }
+
+    /// Remove the counter of `key`, and return it.
+    pub fn remove(&mut self, key: &K) -> Option<HyperLogLog> {
+        self.sketches.remove(key)
+    }
+
+    /// Return the number of keys in the map.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.sketches.len()
+    }
+
+    /// Return `true` if the map doesn't contain any keys.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sketches.is_empty()
+    }
+
+    /// Return an iterator over the keys and their counters, in arbitrary
+    /// order.
+    pub fn iter(&self) -> hash_map::Iter<'_, K, HyperLogLog> {
+        self.sketches.iter()
+    }
+
+    /// Return a counter of all the values inserted into the map, regardless
+    /// of their key.
+    #[must_use]
+    pub fn union(&self) -> HyperLogLog {
+        let mut union = HyperLogLog::new_from_template(&self.template);
+        for hll in self.sketches.values() {
+            union.merge(hll);
+        }
+        union
+    }
+
+    /// Merge another map into the current one, merging the counters of keys
+    /// present in both maps.
+    ///
+    /// Return an error if the counters of the maps don't have the same
+    /// parameters and seed.
+    pub fn merge(&mut self, src: &HllMap<K>) -> Result<(), Error>
+    where
+        K: Clone,
+    {
+        self.template.check_compatible(&src.template)?;
+        for (key, hll) in &src.sketches {
+            match self.sketches.get_mut(key) {
+                Some(dst) => dst.merge(hll),
+                None => {
+                    self.sketches.insert(key.clone(), hll.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove all the keys from the map.
+    pub fn clear(&mut self) {
+        self.sketches.clear();
+    }
+
+    fn entry(&mut self, key: K) -> &mut HyperLogLog {
+        let template = &self.template;
+        self.sketches
+            .entry(key)
+            .or_insert_with(|| HyperLogLog::new_from_template(template))
+    }
+}
+
+impl<'a, K: Eq + Hash> IntoIterator for &'a HllMap<K> {
+    type Item = (&'a K, &'a HyperLogLog);
+    type IntoIter = hash_map::Iter<'a, K, HyperLogLog>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[test]
+fn map_test_group_by() {
+    let mut map = HllMap::new_deterministic(0.001, 1);
+    assert!(map.is_empty());
+    for x in 0..30_000u32 {
+        map.insert(x % 3, &(x / 3));
+        map.insert_by_hash_value(3, u64::from(x % 10));
+    }
+    assert_eq!(map.len(), 4);
+    for key in 0..3 {
+        assert!((map.estimate(&key) - 10_000.0).abs() < 500.0);
+    }
+    assert!((map.estimate(&3) - 10.0).abs() < 0.5);
+    assert_eq!(map.estimate(&4), 0.0);
+    assert!((map.union().len() - 10_010.0).abs() < 500.0);
+    assert_eq!(map.iter().count(), 4);
+
+    let mut other = HllMap::with_template(map.get(&0).unwrap());
+    other.insert(0, &1_000_000u32);
+    other.insert(5, &0u32);
+    map.merge(&other).unwrap();
+    assert_eq!(map.len(), 5);
+    assert!((map.estimate(&5) - 1.0).abs() < 0.1);
+    assert!(map.remove(&5).is_some());
+
+    let incompatible = HllMap::new_deterministic(0.001, 2);
+    assert_eq!(map.merge(&incompatible), Err(Error::IncompatibleSeed));
+    map.clear();
+    assert!(map.is_empty());
+}
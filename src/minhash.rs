@@ -0,0 +1,216 @@
+//! MinHash signatures, for similarity estimation and locality-sensitive
+//! hashing.
+
+use std::hash::{Hash, Hasher};
+
+use crate::{Error, HashConfig};
+
+/// A one-permutation MinHash signature with optimal densification, as
+/// described in "Optimal Densification for Fast and Accurate Minwise
+/// Hashing", Shrivastava, 2017.
+///
+/// Hashes are split into `k` bins, each keeping its smallest hash. Empty bins
+/// are filled from other bins when the signature is computed, so every
+/// value only needs to be hashed once, and signatures can still be merged
+/// exactly. The fraction of identical components of two signatures is an
+/// estimate of the Jaccard index of the sets, with a standard error of about
+/// `1/sqrt(k)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MinHash {
+    hash: HashConfig,
+    mins: Vec<u64>,
+}
+
+crate::hash::zeroize_on_drop!(MinHash);
+
+impl MinHash {
+    /// Create a new signature with `k` components, with the given seed.
+    #[must_use]
+    pub fn new_deterministic(k: usize, seed: u128) -> Self {
+        Self::new_with_hash_config(k, HashConfig::with_seed(seed))
+    }
+
+    /// Create a new signature with `k` components, using the given hash
+    /// function.
+    #[must_use]
+    pub fn new_with_hash_config(k: usize, hash: HashConfig) -> Self {
+        assert!(k > 0);
+        MinHash {
+            hash,
+            mins: vec![u64::MAX; k],
+        }
+    }
+
+    /// Create a new signature with `k` components, with a random seed.
+    /// Requires the `random` feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(k: usize) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(k, seed)
+    }
+
+    /// Create a new, empty signature with the same parameters as an existing
+    /// one.
+    #[must_use]
+    pub fn new_from_template(minhash: &MinHash) -> Self {
+        Self::new_with_hash_config(minhash.k(), minhash.hash)
+    }
+
+    /// Return the number of components of the signature.
+    #[must_use]
+    pub fn k(&self) -> usize {
+        self.mins.len()
+    }
+
+    /// Return the hash function used by the signature.
+    #[must_use]
+    pub fn hash_config(&self) -> HashConfig {
+        self.hash
+    }
+
+    /// Insert a new value into the signature.
+    pub fn insert<V: Hash>(&mut self, value: &V) {
+        let mut sip = crate::hash::new_hasher(self.hash);
+        value.hash(&mut sip);
+        self.insert_by_hash_value(sip.finish());
+    }
+
+    /// Insert all the values of an iterator into the signature.
+    pub fn insert_all<I>(&mut self, values: I)
+    where
+        I: IntoIterator,
+        I::Item: Hash,
+    {
+        for value in values {
+            self.insert(&value);
+        }
+    }
+
+    /// Insert a new u64 value into the signature.
+    pub fn insert_by_hash_value(&mut self, x: u64) {
+        let bin = ((u128::from(x) * self.mins.len() as u128) >> 64) as usize;
+        let min = &mut self.mins[bin];
+        *min = (*min).min(x);
+    }
+
+    /// Return `true` if no values were inserted into the signature.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.mins.iter().all(|&x| x == u64::MAX)
+    }
+
+    /// Return the densified signature, with `k` components.
+    ///
+    /// Each empty bin takes the value of a non-empty bin chosen by a
+    /// deterministic sequence only depending on its index, so that signatures
+    /// of different sets remain comparable. The components of an empty
+    /// signature are all `u64::MAX`.
+    #[must_use]
+    pub fn signature(&self) -> Vec<u64> {
+        if self.is_empty() {
+            return self.mins.clone();
+        }
+        let k = self.mins.len() as u128;
+        (0..self.mins.len())
+            .map(|i| {
+                let mut attempt = 0u64;
+                let mut j = i;
+                while self.mins[j] == u64::MAX {
+                    attempt += 1;
+                    let r = mix((i as u64) << 32 ^ attempt);
+                    j = ((u128::from(r) * k) >> 64) as usize;
+                }
+                self.mins[j]
+            })
+            .collect()
+    }
+
+    /// Return the Jaccard index of the sets represented by the two
+    /// signatures.
+    ///
+    /// Return an error if the signatures don't have the same number of
+    /// components and hash function.
+    pub fn jaccard(&self, other: &MinHash) -> Result<f64, Error> {
+        self.check_compatible(other)?;
+        if self.is_empty() || other.is_empty() {
+            return Ok(0.0);
+        }
+        let shared = self
+            .signature()
+            .iter()
+            .zip(other.signature())
+            .filter(|&(&a, b)| a == b)
+            .count();
+        Ok(shared as f64 / self.k() as f64)
+    }
+
+    /// Merge another signature into the current one, so that it represents
+    /// the union of both sets.
+    ///
+    /// Return an error if the signatures are not compatible.
+    pub fn merge(&mut self, src: &MinHash) -> Result<(), Error> {
+        self.check_compatible(src)?;
+        for (min, &x) in self.mins.iter_mut().zip(&src.mins) {
+            *min = (*min).min(x);
+        }
+        Ok(())
+    }
+
+    /// Wipe the signature.
+    pub fn clear(&mut self) {
+        self.mins.iter_mut().for_each(|x| *x = u64::MAX);
+    }
+
+    fn check_compatible(&self, other: &MinHash) -> Result<(), Error> {
+        if other.k() != self.k() {
+            return Err(Error::IncompatiblePrecision);
+        }
+        self.hash.check_compatible(other.hash)
+    }
+}
+
+fn mix(mut x: u64) -> u64 {
+    x ^= x >> 30;
+    x = x.wrapping_mul(0xbf58476d1ce4e5b9);
+    x ^= x >> 27;
+    x = x.wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+#[test]
+fn minhash_test_jaccard() {
+    let mut a = MinHash::new_deterministic(1024, 1);
+    let mut b = MinHash::new_from_template(&a);
+    assert!(a.is_empty());
+    assert_eq!(a.jaccard(&b), Ok(0.0));
+    a.insert_all(0..100_000u32);
+    b.insert_all(50_000..150_000u32);
+    assert!((a.jaccard(&b).unwrap() - 1.0 / 3.0).abs() < 0.05);
+    assert_eq!(a.jaccard(&a), Ok(1.0));
+
+    let mut expected = MinHash::new_from_template(&a);
+    expected.insert_all(0..150_000u32);
+    a.merge(&b).unwrap();
+    assert_eq!(a, expected);
+
+    let other = MinHash::new_deterministic(1024, 2);
+    assert_eq!(a.merge(&other), Err(Error::IncompatibleSeed));
+    let other = MinHash::new_deterministic(512, 1);
+    assert_eq!(a.jaccard(&other), Err(Error::IncompatiblePrecision));
+    a.clear();
+    assert!(a.is_empty());
+}
+
+#[test]
+fn minhash_test_densification() {
+    let mut a = MinHash::new_deterministic(256, 1);
+    let mut b = MinHash::new_from_template(&a);
+    a.insert_all(0..40u32);
+    b.insert_all(20..60u32);
+    let signature = a.signature();
+    assert_eq!(signature.len(), 256);
+    assert!(signature.iter().all(|&x| x != u64::MAX));
+    assert!((a.jaccard(&b).unwrap() - 1.0 / 3.0).abs() < 0.15);
+}
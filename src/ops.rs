@@ -0,0 +1,233 @@
+//! Set operations over several `HyperLogLog` counters.
+
+use crate::{Error, HyperLogLog};
+
+/// An estimate of the cardinality of an intersection, with its propagated
+/// standard error, returned by [`intersection_many`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct IntersectionEstimate {
+    /// The estimated cardinality of the intersection, clamped to zero.
+    pub estimate: f64,
+    /// The standard error of the estimate, propagated from the standard
+    /// errors of the union estimates it was computed from.
+    pub std_error: f64,
+}
+
+impl IntersectionEstimate {
+    /// Return `true` if the estimate is larger than twice its standard
+    /// error. Otherwise, the estimate is statistically meaningless: it can't
+    /// be distinguished from an empty intersection, or from an intersection
+    /// twice as large.
+    #[must_use]
+    pub fn is_significant(&self) -> bool {
+        self.estimate > 2.0 * self.std_error
+    }
+}
+
+/// Estimate the cardinality of the intersection of the sets represented by
+/// `hlls`, using the inclusion–exclusion principle.
+///
+/// The cardinality of the union of every non-empty subset of the counters
+/// is estimated, so the cost is exponential in the number of counters, and
+/// at most 20 counters are accepted. Errors of the union estimates add up,
+/// so the standard error of the result grows with the number of counters
+/// and the size of their union, regardless of the size of the intersection.
+/// Use [`IntersectionEstimate::is_significant`] to check that the result
+/// is meaningful.
+///
+/// Return an error if more than 20 counters are given, or if they don't
+/// have the same parameters and seed.
+pub fn intersection_many(hlls: &[&HyperLogLog]) -> Result<IntersectionEstimate, Error> {
+    if hlls.len() > 20 {
+        return Err(Error::TooManyCounters);
+    }
+    let first = match hlls.first() {
+        Some(first) => first,
+        None => {
+            return Ok(IntersectionEstimate {
+                estimate: 0.0,
+                std_error: 0.0,
+            })
+        }
+    };
+    for hll in &hlls[1..] {
+        first.check_compatible(hll)?;
+    }
+    let error_rate = first.expected_error();
+    let (mut estimate, mut variance) = (0.0, 0.0);
+    let mut registers = vec![0u8; first.m];
+    for mask in 1..1usize << hlls.len() {
+        registers.iter_mut().for_each(|x| *x = 0);
+        for (i, hll) in hlls.iter().enumerate() {
+            if mask & (1 << i) != 0 {
+                for (mir, &x) in registers.iter_mut().zip(hll.M.iter()) {
+                    *mir = (*mir).max(x);
+                }
+            }
+        }
+        let union = HyperLogLog::estimate_with(
+            first.p,
+            first.alpha,
+            HyperLogLog::vec_count_zero(&registers),
+            || HyperLogLog::harmonic_sum(&registers),
+        );
+        if mask.count_ones() % 2 == 1 {
+            estimate += union;
+        } else {
+            estimate -= union;
+        }
+        variance += (union * error_rate).powi(2);
+    }
+    Ok(IntersectionEstimate {
+        estimate: estimate.max(0.0),
+        std_error: variance.sqrt(),
+    })
+}
+
+/// The pairwise similarities of a set of counters, returned by
+/// [`similarity_matrix`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimilarityMatrix {
+    lens: Vec<f64>,
+    // Row-major estimates of the pairwise intersection cardinalities.
+    intersections: Vec<f64>,
+}
+
+impl SimilarityMatrix {
+    /// Return the number of counters.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.lens.len()
+    }
+
+    /// Return the cardinality of the counter `i`.
+    #[must_use]
+    pub fn len(&self, i: usize) -> f64 {
+        self.lens[i]
+    }
+
+    /// Return `true` if the matrix was computed from an empty set of
+    /// counters.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.lens.is_empty()
+    }
+
+    /// Return the estimated cardinality of the intersection of the sets
+    /// represented by the counters `i` and `j`.
+    #[must_use]
+    pub fn intersection(&self, i: usize, j: usize) -> f64 {
+        self.intersections[i * self.size() + j]
+    }
+
+    /// Return the Jaccard index of the sets represented by the counters `i`
+    /// and `j`, i.e. `|A ∩ B| / |A ∪ B|`.
+    #[must_use]
+    pub fn jaccard(&self, i: usize, j: usize) -> f64 {
+        let intersection = self.intersection(i, j);
+        let union = self.lens[i] + self.lens[j] - intersection;
+        if union <= 0.0 {
+            return 0.0;
+        }
+        (intersection / union).clamp(0.0, 1.0)
+    }
+
+    /// Return the fraction of the set represented by the counter `i` that
+    /// is contained in the set represented by the counter `j`, i.e.
+    /// `|A ∩ B| / |A|`.
+    #[must_use]
+    pub fn containment(&self, i: usize, j: usize) -> f64 {
+        if self.lens[i] == 0.0 {
+            return 0.0;
+        }
+        (self.intersection(i, j) / self.lens[i]).clamp(0.0, 1.0)
+    }
+}
+
+/// Compute the pairwise similarities of the sets represented by `hlls`.
+///
+/// Each cardinality and each pairwise union is only estimated once, and
+/// the Jaccard indices and containments are derived from them by
+/// inclusion–exclusion.
+///
+/// Return an error if the counters don't have the same parameters and seed.
+pub fn similarity_matrix(hlls: &[&HyperLogLog]) -> Result<SimilarityMatrix, Error> {
+    if let Some(first) = hlls.first() {
+        for hll in &hlls[1..] {
+            first.check_compatible(hll)?;
+        }
+    }
+    let n = hlls.len();
+    let lens: Vec<f64> = hlls.iter().map(|hll| hll.len()).collect();
+    let mut intersections = vec![0.0; n * n];
+    for i in 0..n {
+        intersections[i * n + i] = lens[i];
+        for j in i + 1..n {
+            let union = hlls[i].union_cardinality(hlls[j]);
+            let intersection = (lens[i] + lens[j] - union).clamp(0.0, lens[i].min(lens[j]));
+            intersections[i * n + j] = intersection;
+            intersections[j * n + i] = intersection;
+        }
+    }
+    Ok(SimilarityMatrix {
+        lens,
+        intersections,
+    })
+}
+
+#[cfg(any(feature = "with_bias_tables", feature = "polynomial_bias"))]
+#[test]
+fn ops_test_intersection_many() {
+    let template = HyperLogLog::new_deterministic(0.0005, 1);
+    let hlls: Vec<_> = (0..3u32)
+        .map(|i| {
+            let mut hll = HyperLogLog::new_from_template(&template);
+            hll.insert_all(i * 10_000..i * 10_000 + 50_000);
+            hll
+        })
+        .collect();
+    let refs: Vec<_> = hlls.iter().collect();
+    let result = intersection_many(&refs).unwrap();
+    assert!((result.estimate - 30_000.0).abs() < 3.0 * result.std_error);
+    assert!(result.is_significant());
+
+    let mut disjoint = HyperLogLog::new_from_template(&template);
+    disjoint.insert_all(1_000_000..1_001_000u32);
+    let result = intersection_many(&[&hlls[0], &hlls[1], &disjoint]).unwrap();
+    assert!(!result.is_significant());
+
+    assert_eq!(intersection_many(&[]).unwrap().estimate, 0.0);
+    let other = HyperLogLog::new_deterministic(0.0005, 2);
+    assert_eq!(
+        intersection_many(&[&hlls[0], &other]),
+        Err(Error::IncompatibleSeed)
+    );
+    assert_eq!(
+        intersection_many(&[&hlls[0]; 21]),
+        Err(Error::TooManyCounters)
+    );
+}
+
+#[cfg(any(feature = "with_bias_tables", feature = "polynomial_bias"))]
+#[test]
+fn ops_test_similarity_matrix() {
+    let template = HyperLogLog::new_deterministic(0.001, 1);
+    let hlls: Vec<_> = [(0, 20_000u32), (10_000, 30_000), (100_000, 110_000)]
+        .iter()
+        .map(|&(start, end)| {
+            let mut hll = HyperLogLog::new_from_template(&template);
+            hll.insert_all(start..end);
+            hll
+        })
+        .collect();
+    let refs: Vec<_> = hlls.iter().collect();
+    let matrix = similarity_matrix(&refs).unwrap();
+    assert_eq!(matrix.size(), 3);
+    assert!((matrix.jaccard(0, 1) - 1.0 / 3.0).abs() < 0.05);
+    assert!((matrix.jaccard(1, 0) - matrix.jaccard(0, 1)).abs() < f64::EPSILON);
+    assert!((matrix.containment(1, 0) - 0.5).abs() < 0.05);
+    assert!(matrix.jaccard(0, 2) < 0.02);
+    assert!((matrix.jaccard(2, 2) - 1.0).abs() < f64::EPSILON);
+    assert!(similarity_matrix(&[]).unwrap().is_empty());
+}
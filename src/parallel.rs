@@ -0,0 +1,54 @@
+//! Parallel ingestion using `rayon`.
+
+use std::hash::Hash;
+
+use rayon::prelude::*;
+
+use crate::HyperLogLog;
+
+impl HyperLogLog {
+    /// Insert all the values of a parallel iterator into the `HyperLogLog`
+    /// counter.
+    ///
+    /// Values are split across threads, each of them inserting into an
+    /// empty copy of the counter, and the copies are then merged.
+    pub fn par_insert_all<I>(&mut self, values: I)
+    where
+        I: IntoParallelIterator,
+        I::Item: Hash,
+    {
+        let template = &*self;
+        let partial = values
+            .into_par_iter()
+            .fold(
+                || HyperLogLog::new_from_template(template),
+                |mut hll, value| {
+                    hll.insert(&value);
+                    hll
+                },
+            )
+            .reduce_with(|mut hll, other| {
+                hll.merge(&other);
+                hll
+            });
+        if let Some(partial) = partial {
+            self.merge(&partial);
+        }
+    }
+}
+
+#[test]
+fn parallel_test_par_insert_all() {
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1);
+    let mut expected = HyperLogLog::new_from_template(&hll);
+    hll.insert(&1_000_000u32);
+    hll.par_insert_all(0..100_000u32);
+    expected.insert_all(0..100_000u32);
+    expected.insert(&1_000_000u32);
+    assert_eq!(hll.registers(), expected.registers());
+
+    let values: Vec<String> = (0..1000).map(|i| format!("test{}", i)).collect();
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1);
+    hll.par_insert_all(&values);
+    assert!((hll.len() - 1000.0).abs() < 50.0);
+}
@@ -0,0 +1,136 @@
+// Vectorized per-register maximum, used by merge operations, and cache
+// prefetching for bulk insertions.
+
+/// Store `max(dst[i], src[i])` into `dst[i]`, calling `on_change(i)` for
+/// every register that increased.
+#[inline]
+pub(crate) fn max_into(dst: &mut [u8], src: &[u8], on_change: impl FnMut(usize)) {
+    assert_eq!(dst.len(), src.len());
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: AVX2 support was just checked.
+            return unsafe { x86::max_into_avx2(dst, src, on_change) };
+        }
+        // SAFETY: SSE2 is part of the x86_64 baseline.
+        unsafe { x86::max_into_sse2(dst, src, on_change) }
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        // SAFETY: NEON is part of the aarch64 baseline.
+        unsafe { arm::max_into_neon(dst, src, on_change) }
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    max_into_scalar(dst, src, 0, on_change)
+}
+
+/// Hint that the cache line containing `x` will be accessed soon.
+#[inline(always)]
+pub(crate) fn prefetch(x: &u8) {
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: SSE is part of the x86_64 baseline, and prefetching has no
+    // observable effect.
+    unsafe {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>((x as *const u8).cast());
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = x;
+}
+
+#[inline]
+fn max_into_scalar(dst: &mut [u8], src: &[u8], offset: usize, mut on_change: impl FnMut(usize)) {
+    for (i, (mir, &src_mir)) in dst.iter_mut().zip(src).enumerate() {
+        if src_mir > *mir {
+            *mir = src_mir;
+            on_change(offset + i);
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn max_into_sse2(
+        dst: &mut [u8],
+        src: &[u8],
+        mut on_change: impl FnMut(usize),
+    ) {
+        let n = dst.len() / 16 * 16;
+        for i in (0..n).step_by(16) {
+            let a = _mm_loadu_si128(dst.as_ptr().add(i) as *const __m128i);
+            let b = _mm_loadu_si128(src.as_ptr().add(i) as *const __m128i);
+            let max = _mm_max_epu8(a, b);
+            let mut changed = !(_mm_movemask_epi8(_mm_cmpeq_epi8(max, a)) as u32) & 0xffff;
+            if changed != 0 {
+                _mm_storeu_si128(dst.as_mut_ptr().add(i) as *mut __m128i, max);
+                while changed != 0 {
+                    on_change(i + changed.trailing_zeros() as usize);
+                    changed &= changed - 1;
+                }
+            }
+        }
+        super::max_into_scalar(&mut dst[n..], &src[n..], n, on_change);
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn max_into_avx2(
+        dst: &mut [u8],
+        src: &[u8],
+        mut on_change: impl FnMut(usize),
+    ) {
+        let n = dst.len() / 32 * 32;
+        for i in (0..n).step_by(32) {
+            let a = _mm256_loadu_si256(dst.as_ptr().add(i) as *const __m256i);
+            let b = _mm256_loadu_si256(src.as_ptr().add(i) as *const __m256i);
+            let max = _mm256_max_epu8(a, b);
+            let mut changed = !(_mm256_movemask_epi8(_mm256_cmpeq_epi8(max, a)) as u32);
+            if changed != 0 {
+                _mm256_storeu_si256(dst.as_mut_ptr().add(i) as *mut __m256i, max);
+                while changed != 0 {
+                    on_change(i + changed.trailing_zeros() as usize);
+                    changed &= changed - 1;
+                }
+            }
+        }
+        super::max_into_scalar(&mut dst[n..], &src[n..], n, on_change);
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arm {
+    use std::arch::aarch64::*;
+
+    pub(super) unsafe fn max_into_neon(
+        dst: &mut [u8],
+        src: &[u8],
+        mut on_change: impl FnMut(usize),
+    ) {
+        let n = dst.len() / 16 * 16;
+        for i in (0..n).step_by(16) {
+            let a = vld1q_u8(dst.as_ptr().add(i));
+            let b = vld1q_u8(src.as_ptr().add(i));
+            // All lanes of `src` are lower or equal: nothing to do.
+            if vmaxvq_u8(vcgtq_u8(b, a)) == 0 {
+                continue;
+            }
+            super::max_into_scalar(&mut dst[i..i + 16], &src[i..i + 16], i, &mut on_change);
+        }
+        super::max_into_scalar(&mut dst[n..], &src[n..], n, on_change);
+    }
+}
+
+#[test]
+fn simd_test_max_into() {
+    let mut dst: Vec<u8> = (0..1000u32).map(|i| (i * 7 % 13) as u8).collect();
+    let src: Vec<u8> = (0..1000u32).map(|i| (i * 5 % 11) as u8).collect();
+    let mut expected = dst.clone();
+    let mut expected_changes = vec![];
+    max_into_scalar(&mut expected, &src, 0, |i| expected_changes.push(i));
+    let mut changes = vec![];
+    max_into(&mut dst, &src, |i| changes.push(i));
+    assert_eq!(dst, expected);
+    assert_eq!(changes, expected_changes);
+}
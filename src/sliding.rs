@@ -0,0 +1,303 @@
+//! `HyperLogLog` counters over sliding time or count windows.
+
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+
+use crate::{HashConfig, HyperLogLog};
+
+/// A HyperLogLog counter estimating the number of distinct values inserted
+/// during the last `window` time units, for any window up to a maximum set
+/// at creation.
+///
+/// Instead of a single value, each register keeps the list of its possible
+/// future maxima: the `(timestamp, value)` pairs that would be the maximum
+/// of the register if all the older pairs expired. Timestamps increase and
+/// values decrease along the list, which holds about `ln(n)` pairs for `n`
+/// values hashed to the register within the window.
+///
+/// Timestamps are arbitrary `u64` units, such as seconds, and are expected
+/// to be non-decreasing. A timestamp older than the latest one is treated
+/// as the latest one. Queries are only exact for windows ending at or after
+/// the latest timestamp, since values that were overwritten by newer ones
+/// are forgotten.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SlidingHyperLogLog {
+    p: u8,
+    hash: HashConfig,
+    window: u64,
+    now: u64,
+    M: Vec<VecDeque<(u64, u8)>>,
+}
+
+crate::hash::zeroize_on_drop!(SlidingHyperLogLog);
+
+impl SlidingHyperLogLog {
+    /// Create a new `SlidingHyperLogLog` counter with the given error rate,
+    /// maximum window and seed.
+    #[must_use]
+    pub fn new_deterministic(error_rate: f64, window: u64, seed: u128) -> Self {
+        let p = HyperLogLog::precision_for_error_rate(error_rate);
+        SlidingHyperLogLog {
+            p,
+            hash: HashConfig::with_seed(seed),
+            window,
+            now: 0,
+            M: vec![VecDeque::new(); 1usize << p],
+        }
+    }
+
+    /// Create a new `SlidingHyperLogLog` counter with the given error rate,
+    /// maximum window and a random seed. Requires the `random` feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(error_rate: f64, window: u64) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(error_rate, window, seed)
+    }
+
+    /// Return the maximum window.
+    #[must_use]
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    /// Insert a new value, seen at `timestamp`.
+    pub fn insert<V: Hash>(&mut self, value: &V, timestamp: u64) {
+        let mut sip = crate::hash::new_hasher(self.hash);
+        value.hash(&mut sip);
+        self.insert_by_hash_value(sip.finish(), timestamp);
+    }
+
+    /// Insert a new u64 value, seen at `timestamp`.
+    pub fn insert_by_hash_value(&mut self, x: u64, timestamp: u64) {
+        let now = timestamp.max(self.now);
+        self.now = now;
+        let (j, rho) = HyperLogLog::register_update(x, self.p);
+        let pairs = &mut self.M[j];
+        // Older pairs with a smaller or equal value can never be the maximum
+        // again.
+        while pairs.back().is_some_and(|&(_, r)| r <= rho) {
+            pairs.pop_back();
+        }
+        pairs.push_back((now, rho));
+        Self::expire_pairs(pairs, now, self.window);
+    }
+
+    /// Return the number of distinct values inserted during the maximum
+    /// window ending at `now`.
+    #[must_use]
+    pub fn len(&self, now: u64) -> f64 {
+        self.len_window(now, self.window)
+    }
+
+    /// Return the number of distinct values inserted during the `window`
+    /// time units ending at `now`, i.e. with a timestamp `t` such that
+    /// `now - window < t <= now`. `window` is capped to the maximum window.
+    #[must_use]
+    pub fn len_window(&self, now: u64, window: u64) -> f64 {
+        self.to_hyperloglog(now, window).len()
+    }
+
+    /// Return `true` if no values were inserted during the maximum window
+    /// ending at `now`.
+    #[must_use]
+    pub fn is_empty(&self, now: u64) -> bool {
+        self.M.iter().all(|pairs| {
+            pairs
+                .back()
+                .is_none_or(|&(t, _)| !Self::in_window(t, now, self.window))
+        })
+    }
+
+    /// Return a regular `HyperLogLog` counter with the values inserted
+    /// during the `window` time units ending at `now`. `window` is capped to
+    /// the maximum window.
+    #[must_use]
+    pub fn to_hyperloglog(&self, now: u64, window: u64) -> HyperLogLog {
+        let window = window.min(self.window);
+        let M = self
+            .M
+            .iter()
+            .map(|pairs| {
+                pairs
+                    .iter()
+                    .find(|&&(t, _)| t <= now && Self::in_window(t, now, window))
+                    .map_or(0, |&(_, rho)| rho)
+            })
+            .collect();
+        HyperLogLog::from_parts(self.p, self.hash, M)
+    }
+
+    /// Drop the values that are outside the maximum window ending at `now`.
+    pub fn expire(&mut self, now: u64) {
+        for pairs in &mut self.M {
+            Self::expire_pairs(pairs, now, self.window);
+        }
+    }
+
+    /// Wipe the counter.
+    pub fn clear(&mut self) {
+        for pairs in &mut self.M {
+            pairs.clear();
+        }
+        self.now = 0;
+    }
+
+    fn expire_pairs(pairs: &mut VecDeque<(u64, u8)>, now: u64, window: u64) {
+        while pairs
+            .front()
+            .is_some_and(|&(t, _)| !Self::in_window(t, now, window))
+        {
+            pairs.pop_front();
+        }
+    }
+
+    fn in_window(t: u64, now: u64, window: u64) -> bool {
+        t.saturating_add(window) > now
+    }
+}
+
+/// A HyperLogLog counter estimating the number of distinct values among
+/// the last `window` inserted values, for any window up to a maximum set at
+/// creation.
+///
+/// This is a [`SlidingHyperLogLog`] using the insertion count as a clock.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CountWindowHyperLogLog {
+    sliding: SlidingHyperLogLog,
+    count: u64,
+}
+
+impl CountWindowHyperLogLog {
+    /// Create a new `CountWindowHyperLogLog` counter with the given error
+    /// rate, maximum window and seed.
+    #[must_use]
+    pub fn new_deterministic(error_rate: f64, window: u64, seed: u128) -> Self {
+        CountWindowHyperLogLog {
+            sliding: SlidingHyperLogLog::new_deterministic(error_rate, window, seed),
+            count: 0,
+        }
+    }
+
+    /// Create a new `CountWindowHyperLogLog` counter with the given error
+    /// rate, maximum window and a random seed. Requires the `random` feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(error_rate: f64, window: u64) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(error_rate, window, seed)
+    }
+
+    /// Insert a new value.
+    pub fn insert<V: Hash>(&mut self, value: &V) {
+        self.count += 1;
+        self.sliding.insert(value, self.count);
+    }
+
+    /// Insert a new u64 value.
+    pub fn insert_by_hash_value(&mut self, x: u64) {
+        self.count += 1;
+        self.sliding.insert_by_hash_value(x, self.count);
+    }
+
+    /// Return the number of values inserted so far.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Return the number of distinct values among the last `window` values,
+    /// where `window` is the maximum window.
+    #[must_use]
+    pub fn len(&self) -> f64 {
+        self.sliding.len(self.count)
+    }
+
+    /// Return the number of distinct values among the last `window` values.
+    /// `window` is capped to the maximum window.
+    #[must_use]
+    pub fn len_window(&self, window: u64) -> f64 {
+        self.sliding.len_window(self.count, window)
+    }
+
+    /// Return `true` if no values were inserted.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.count == 0 || self.sliding.window() == 0
+    }
+
+    /// Return a regular `HyperLogLog` counter with the last `window` values.
+    /// `window` is capped to the maximum window.
+    #[must_use]
+    pub fn to_hyperloglog(&self, window: u64) -> HyperLogLog {
+        self.sliding.to_hyperloglog(self.count, window)
+    }
+
+    /// Wipe the counter.
+    pub fn clear(&mut self) {
+        self.sliding.clear();
+        self.count = 0;
+    }
+}
+
+#[test]
+fn sliding_test_window() {
+    let mut hll = SlidingHyperLogLog::new_deterministic(0.001, 100, 1);
+    assert!(hll.is_empty(0));
+    for t in 0..100u64 {
+        for i in 0..100u64 {
+            hll.insert(&(t * 100 + i), t);
+        }
+    }
+    assert!((hll.len(99) - 10_000.0).abs() < 500.0);
+    assert!((hll.len_window(99, 10) - 1_000.0).abs() < 50.0);
+    assert!((hll.len_window(99, 1000) - 10_000.0).abs() < 500.0);
+    assert!((hll.len(149) - 5_000.0).abs() < 250.0);
+    assert!((hll.len_window(149, 10) - 0.0).abs() < f64::EPSILON);
+    assert!(!hll.is_empty(198));
+    assert!(hll.is_empty(199));
+
+    let mut expected = HyperLogLog::new_deterministic(0.001, 1);
+    expected.insert_all(9_000..10_000u64);
+    assert_eq!(hll.to_hyperloglog(99, 10), expected);
+
+    hll.expire(150);
+    assert!(hll.M.iter().flatten().all(|&(t, _)| t > 50));
+    assert!((hll.len(150) - 4_900.0).abs() < 250.0);
+    hll.clear();
+    assert!(hll.is_empty(0));
+}
+
+#[test]
+fn sliding_test_repeated_values() {
+    let mut hll = SlidingHyperLogLog::new_deterministic(0.001, 10, 1);
+    for t in 0..1000u64 {
+        hll.insert(&(t % 20), t);
+    }
+    assert!((hll.len(999) - 10.0).abs() < 1.0);
+    assert!(hll.M.iter().all(|pairs| pairs.len() <= 10));
+}
+
+#[test]
+fn sliding_test_count_window() {
+    let mut hll = CountWindowHyperLogLog::new_deterministic(0.001, 1000, 1);
+    assert!(hll.is_empty());
+    for i in 0..10_000u32 {
+        hll.insert(&i);
+    }
+    assert_eq!(hll.count(), 10_000);
+    assert!((hll.len() - 1000.0).abs() < 50.0);
+    assert!((hll.len_window(100) - 100.0).abs() < 5.0);
+    for i in 0..10_000u32 {
+        hll.insert(&(i % 10));
+    }
+    assert!((hll.len() - 10.0).abs() < 0.5);
+
+    let mut expected = HyperLogLog::new_deterministic(0.001, 1);
+    expected.insert_all(0..10u32);
+    assert_eq!(hll.to_hyperloglog(1000), expected);
+    hll.clear();
+    assert!(hll.is_empty());
+}
@@ -0,0 +1,472 @@
+//! Pluggable storage for the registers of a counter.
+
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+use crate::{Changes, Error, HashConfig, HyperLogLog, MAX_PRECISION, MIN_PRECISION};
+
+/// The storage of the registers of a [`HyperLogLog`] counter.
+///
+/// Implementations only have to store small integers: the estimator logic
+/// is shared by all of them. Register values never exceed 61.
+pub trait RegisterStore {
+    /// `true` if the registers can also be updated through a shared
+    /// reference, with [`SharedRegisterStore`]. Estimates of counters using
+    /// such stores are not cached, since registers can change while an
+    /// estimate is being computed.
+    const SHARED: bool = false;
+
+    /// Return the number of registers.
+    fn len(&self) -> usize;
+
+    /// Return `true` if there are no registers.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the value of the register `j`.
+    fn register(&self, j: usize) -> u8;
+
+    /// Set the register `j` to `value` if it is larger than the current
+    /// one, and return `true` if the register was updated.
+    fn set_max(&mut self, j: usize, value: u8) -> bool;
+
+    /// Return an iterator over the values of the registers, in order.
+    fn registers(&self) -> impl Iterator<Item = u8> + '_;
+
+    /// Return the number of registers set to zero.
+    fn count_zeros(&self) -> usize {
+        self.registers().filter(|&x| x == 0).count()
+    }
+
+    /// Set all the registers to zero.
+    fn reset(&mut self);
+
+    /// Return the registers as a byte slice, if they are stored one per
+    /// byte. This lets estimates and merges use vectorized code.
+    fn as_bytes(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Return the registers as a mutable byte slice, if they are stored one
+    /// per byte.
+    fn as_bytes_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+}
+
+/// A register store that can be updated through a shared reference, so
+/// that a counter can be updated by several threads without locks.
+///
+/// Implementations must set [`RegisterStore::SHARED`] to `true`.
+pub trait SharedRegisterStore: RegisterStore + Sync {
+    /// Set the register `j` to `value` if it is larger than the current
+    /// one, and return `true` if the register was updated.
+    fn set_max_shared(&self, j: usize, value: u8) -> bool;
+
+    /// Set all the registers to zero through a shared reference.
+    fn reset_shared(&self);
+}
+
+impl RegisterStore for Vec<u8> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    #[inline]
+    fn register(&self, j: usize) -> u8 {
+        self[j]
+    }
+
+    #[inline]
+    fn set_max(&mut self, j: usize, value: u8) -> bool {
+        let mjr = &mut self[j];
+        if value > *mjr {
+            *mjr = value;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn registers(&self) -> impl Iterator<Item = u8> + '_ {
+        self.as_slice().iter().copied()
+    }
+
+    fn count_zeros(&self) -> usize {
+        HyperLogLog::vec_count_zero(self)
+    }
+
+    fn reset(&mut self) {
+        self.fill(0);
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        Some(self)
+    }
+
+    fn as_bytes_mut(&mut self) -> Option<&mut [u8]> {
+        Some(self)
+    }
+}
+
+/// The default store: registers are shared by clones of a counter, and
+/// copied when one of them is modified.
+impl RegisterStore for Arc<Vec<u8>> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    #[inline]
+    fn register(&self, j: usize) -> u8 {
+        self[j]
+    }
+
+    #[inline]
+    fn set_max(&mut self, j: usize, value: u8) -> bool {
+        if value > self[j] {
+            Arc::make_mut(self)[j] = value;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn registers(&self) -> impl Iterator<Item = u8> + '_ {
+        self.iter().copied()
+    }
+
+    fn count_zeros(&self) -> usize {
+        HyperLogLog::vec_count_zero(self)
+    }
+
+    fn reset(&mut self) {
+        match Arc::get_mut(self) {
+            Some(registers) => registers.fill(0),
+            None => *self = Arc::new(vec![0; self.as_slice().len()]),
+        }
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        Some(self)
+    }
+
+    fn as_bytes_mut(&mut self) -> Option<&mut [u8]> {
+        Some(Arc::make_mut(self).as_mut_slice())
+    }
+}
+
+/// Registers stored inline, for counters that don't allocate, such as
+/// [`HyperLogLogFixed`](crate::HyperLogLogFixed).
+impl<const N: usize> RegisterStore for [u8; N] {
+    fn len(&self) -> usize {
+        N
+    }
+
+    #[inline]
+    fn register(&self, j: usize) -> u8 {
+        self[j]
+    }
+
+    #[inline]
+    fn set_max(&mut self, j: usize, value: u8) -> bool {
+        let mjr = &mut self[j];
+        if value > *mjr {
+            *mjr = value;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn registers(&self) -> impl Iterator<Item = u8> + '_ {
+        self.iter().copied()
+    }
+
+    fn count_zeros(&self) -> usize {
+        HyperLogLog::vec_count_zero(self)
+    }
+
+    fn reset(&mut self) {
+        self.fill(0);
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        Some(self)
+    }
+
+    fn as_bytes_mut(&mut self) -> Option<&mut [u8]> {
+        Some(self)
+    }
+}
+
+impl RegisterStore for Vec<AtomicU8> {
+    const SHARED: bool = true;
+
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    #[inline]
+    fn register(&self, j: usize) -> u8 {
+        self[j].load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn set_max(&mut self, j: usize, value: u8) -> bool {
+        self.set_max_shared(j, value)
+    }
+
+    fn registers(&self) -> impl Iterator<Item = u8> + '_ {
+        self.as_slice().iter().map(|x| x.load(Ordering::Relaxed))
+    }
+
+    fn reset(&mut self) {
+        self.reset_shared();
+    }
+}
+
+impl SharedRegisterStore for Vec<AtomicU8> {
+    #[inline]
+    fn set_max_shared(&self, j: usize, value: u8) -> bool {
+        let mjr = &self[j];
+        // Avoid a read-modify-write when the register is already large enough.
+        value > mjr.load(Ordering::Relaxed) && value > mjr.fetch_max(value, Ordering::Relaxed)
+    }
+
+    fn reset_shared(&self) {
+        for mir in self {
+            mir.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Registers packed as 6-bit integers, using 25% less memory than one byte
+/// per register.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PackedRegisters {
+    m: usize,
+    bytes: Vec<u8>,
+}
+
+impl PackedRegisters {
+    const BITS: usize = 6;
+    const MASK: u16 = (1 << Self::BITS) - 1;
+
+    /// Create `m` packed registers, set to zero.
+    #[must_use]
+    pub fn new(m: usize) -> Self {
+        // An extra byte lets every register be read as a 16-bit word.
+        PackedRegisters {
+            m,
+            bytes: vec![0; (m * Self::BITS).div_ceil(8) + 1],
+        }
+    }
+
+    #[inline]
+    fn position(j: usize) -> (usize, u32) {
+        let bit = j * Self::BITS;
+        (bit / 8, (bit % 8) as u32)
+    }
+}
+
+impl RegisterStore for PackedRegisters {
+    fn len(&self) -> usize {
+        self.m
+    }
+
+    #[inline]
+    fn register(&self, j: usize) -> u8 {
+        assert!(j < self.m);
+        let (i, shift) = Self::position(j);
+        let word = u16::from_le_bytes([self.bytes[i], self.bytes[i + 1]]);
+        ((word >> shift) & Self::MASK) as u8
+    }
+
+    #[inline]
+    fn set_max(&mut self, j: usize, value: u8) -> bool {
+        if value <= self.register(j) {
+            return false;
+        }
+        let (i, shift) = Self::position(j);
+        let mut word = u16::from_le_bytes([self.bytes[i], self.bytes[i + 1]]);
+        word = (word & !(Self::MASK << shift)) | ((value as u16 & Self::MASK) << shift);
+        self.bytes[i..i + 2].copy_from_slice(&word.to_le_bytes());
+        true
+    }
+
+    fn registers(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..self.m).map(move |j| self.register(j))
+    }
+
+    fn reset(&mut self) {
+        self.bytes.fill(0);
+    }
+}
+
+impl<S: RegisterStore> HyperLogLog<S> {
+    /// Create a new counter using the registers of `store`, and the given
+    /// hash function.
+    ///
+    /// Return an error if the number of registers is not a power of two in
+    /// the supported range, if a register has an invalid value, or if the
+    /// hash function is not enabled in this build.
+    pub fn with_store(store: S, hash: HashConfig) -> Result<Self, Error> {
+        let m = store.len();
+        let p = m.trailing_zeros() as u8;
+        if !m.is_power_of_two() || !(MIN_PRECISION..=MAX_PRECISION).contains(&p) {
+            return Err(Error::InvalidRegisterCount);
+        }
+        if store.registers().any(|x| x > 64 - p + 1) {
+            return Err(Error::InvalidRegisterValue);
+        }
+        hash.check_supported()?;
+        Ok(HyperLogLog {
+            alpha: HyperLogLog::get_alpha(p),
+            p,
+            m,
+            M: store,
+            hash,
+            changes: Changes::default(),
+        })
+    }
+
+    /// Return the register storage.
+    #[must_use]
+    pub fn store(&self) -> &S {
+        &self.M
+    }
+
+    /// Return a counter with the same registers, using the default store.
+    #[must_use]
+    pub fn to_hyperloglog(&self) -> HyperLogLog {
+        HyperLogLog::from_parts(self.p, self.hash, self.M.registers().collect())
+    }
+}
+
+impl<S: SharedRegisterStore> HyperLogLog<S> {
+    /// Insert a new value into the counter through a shared reference, so
+    /// that several threads can update the same counter.
+    ///
+    /// These updates are not seen by change tracking.
+    ///
+    /// Return `true` if a register was updated.
+    pub fn insert_shared<V: Hash>(&self, value: &V) -> bool {
+        let mut sip = crate::hash::new_hasher(self.hash);
+        value.hash(&mut sip);
+        self.insert_by_hash_value_shared(sip.finish())
+    }
+
+    /// Insert a new u64 value into the counter through a shared reference.
+    /// See [`insert_shared`](Self::insert_shared).
+    ///
+    /// Return `true` if a register was updated.
+    pub fn insert_by_hash_value_shared(&self, x: u64) -> bool {
+        const { assert!(S::SHARED, "Shared stores must set RegisterStore::SHARED") };
+        let (j, rho) = HyperLogLog::register_update(x, self.p);
+        self.M.set_max_shared(j, rho)
+    }
+
+    /// Merge another counter into the current one through a shared
+    /// reference, whatever its register store.
+    ///
+    /// Return an error if the counters don't have the same parameters and
+    /// hash function.
+    pub fn merge_shared<T: RegisterStore>(&self, src: &HyperLogLog<T>) -> Result<(), Error> {
+        self.check_compatible(src)?;
+        for (j, x) in src.M.registers().enumerate() {
+            self.M.set_max_shared(j, x);
+        }
+        Ok(())
+    }
+
+    /// Wipe the counter through a shared reference.
+    pub fn clear_shared(&self) {
+        self.M.reset_shared();
+    }
+}
+
+#[test]
+fn store_test_backends() {
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1);
+    let hash = hll.hash_config();
+    let m = hll.num_registers();
+    let mut bytes = HyperLogLog::with_store(vec![0u8; m], hash).unwrap();
+    let mut packed = HyperLogLog::with_store(PackedRegisters::new(m), hash).unwrap();
+    let atomic = (0..m).map(|_| AtomicU8::new(0)).collect::<Vec<_>>();
+    let mut atomic = HyperLogLog::with_store(atomic, hash).unwrap();
+    assert!(bytes.is_empty() && packed.is_empty() && atomic.is_empty());
+    for i in 0..100_000u32 {
+        hll.insert(&i);
+        bytes.insert(&i);
+        packed.insert(&i);
+        atomic.insert(&i);
+    }
+    assert_eq!(bytes.to_hyperloglog(), hll);
+    assert_eq!(packed.to_hyperloglog(), hll);
+    assert_eq!(atomic.to_hyperloglog(), hll);
+    assert_eq!(packed.len(), hll.len());
+    assert_eq!(atomic.len(), hll.len());
+
+    let mut merged = HyperLogLog::with_store(PackedRegisters::new(m), hash).unwrap();
+    merged.try_merge(&atomic).unwrap();
+    assert_eq!(merged.store(), packed.store());
+    let mut merged = HyperLogLog::new_from_template(&hll);
+    merged.try_merge(&bytes).unwrap();
+    assert_eq!(merged, hll);
+    packed.clear();
+    assert!(packed.is_empty());
+
+    assert_eq!(
+        HyperLogLog::with_store(vec![0u8; 1000], hash).err(),
+        Some(Error::InvalidRegisterCount)
+    );
+    let other = HyperLogLog::new_deterministic(0.001, 2);
+    assert_eq!(bytes.try_merge(&other), Err(Error::IncompatibleSeed));
+}
+
+#[test]
+fn store_test_shared_inserts() {
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1);
+    hll.insert_all(0..100_000u32);
+    let atomic = (0..hll.num_registers())
+        .map(|_| AtomicU8::new(0))
+        .collect::<Vec<_>>();
+    let shared = HyperLogLog::with_store(atomic, hll.hash_config()).unwrap();
+    assert_eq!(shared.len(), 0.0);
+    std::thread::scope(|s| {
+        for t in 0..4u32 {
+            let shared = &shared;
+            s.spawn(move || {
+                for i in (t..100_000).step_by(4) {
+                    shared.insert_shared(&i);
+                }
+            });
+        }
+    });
+    assert_eq!(shared.to_hyperloglog(), hll);
+    assert_eq!(shared.len(), hll.len());
+}
+
+#[test]
+fn store_test_packed_registers() {
+    let mut registers = PackedRegisters::new(37);
+    for j in 0..37 {
+        assert!(registers.set_max(j, (j % 62) as u8 + 1));
+    }
+    for j in 0..37 {
+        assert_eq!(registers.register(j), (j % 62) as u8 + 1);
+        assert!(!registers.set_max(j, 1));
+    }
+    assert!(registers.set_max(5, 61));
+    assert_eq!(
+        (
+            registers.register(4),
+            registers.register(5),
+            registers.register(6)
+        ),
+        (5, 61, 7)
+    );
+    assert_eq!(registers.count_zeros(), 0);
+}
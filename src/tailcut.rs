@@ -0,0 +1,245 @@
+//! `HyperLogLog` counters with 4-bit registers, using the TailCut scheme.
+
+use std::hash::{Hash, Hasher};
+
+use crate::{Error, HashConfig, HyperLogLog};
+
+/// A HyperLogLog counter storing two registers per byte, using half the
+/// memory of a `HyperLogLog` counter with the same precision.
+///
+/// This is the register layout of HLL-TailCut+: registers hold their offset
+/// from a base value shared by all of them, in 4 bits. The base is raised
+/// whenever no register is left at zero, which keeps offsets small since
+/// register values are concentrated around `log2(n/m)`. Offsets larger than
+/// 15 are cut to 15. This only affects the few registers with the largest
+/// values, whose contribution to the estimate is negligible.
+///
+/// Estimates are computed on the reconstructed registers, with the same
+/// estimator as `HyperLogLog` counters, rather than with the estimator
+/// proposed along with the scheme.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TailCutHyperLogLog {
+    p: u8,
+    hash: HashConfig,
+    base: u8,
+    zeros: usize,
+    registers: Vec<u8>,
+}
+
+crate::hash::zeroize_on_drop!(TailCutHyperLogLog);
+
+impl TailCutHyperLogLog {
+    const MAX_OFFSET: u8 = 15;
+
+    /// Create a new counter with the given error rate and seed.
+    #[must_use]
+    pub fn new_deterministic(error_rate: f64, seed: u128) -> Self {
+        Self::new_from_template(&HyperLogLog::new_deterministic(error_rate, seed))
+    }
+
+    /// Create a new counter with the given error rate and a random seed.
+    /// Requires the `random` feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(error_rate: f64) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(error_rate, seed)
+    }
+
+    /// Create a new, empty counter with the same parameters as a
+    /// `HyperLogLog` counter, so that both can be merged.
+    #[must_use]
+    pub fn new_from_template(hll: &HyperLogLog) -> Self {
+        TailCutHyperLogLog {
+            p: hll.p,
+            hash: hll.hash,
+            base: 0,
+            zeros: hll.m,
+            registers: vec![0; hll.m.div_ceil(2)],
+        }
+    }
+
+    /// Return the base value of the registers.
+    #[must_use]
+    pub fn base(&self) -> u8 {
+        self.base
+    }
+
+    /// Return the memory used by the counter, in bytes.
+    #[must_use]
+    pub fn size_in_bytes(&self) -> usize {
+        size_of::<Self>() + self.registers.capacity()
+    }
+
+    /// Insert a new value into the counter.
+    ///
+    /// Return `true` if a register was updated.
+    pub fn insert<V: Hash>(&mut self, value: &V) -> bool {
+        let mut sip = crate::hash::new_hasher(self.hash);
+        value.hash(&mut sip);
+        self.insert_by_hash_value(sip.finish())
+    }
+
+    /// Insert a new u64 value into the counter.
+    ///
+    /// Return `true` if a register was updated.
+    pub fn insert_by_hash_value(&mut self, x: u64) -> bool {
+        let (j, rho) = HyperLogLog::register_update(x, self.p);
+        self.update(j, rho)
+    }
+
+    /// Return the cardinality of the counter.
+    #[must_use]
+    pub fn len(&self) -> f64 {
+        let hist = HyperLogLog::values_histogram(self.values());
+        HyperLogLog::estimate_with(
+            self.p,
+            HyperLogLog::get_alpha(self.p),
+            hist[0] as usize,
+            || HyperLogLog::histogram_sum(&hist),
+        )
+    }
+
+    /// Return `true` if the counter is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.base == 0 && self.zeros == 1usize << self.p
+    }
+
+    /// Merge another counter into the current one.
+    ///
+    /// Return an error if the counters don't have the same parameters and
+    /// seed.
+    pub fn merge(&mut self, src: &TailCutHyperLogLog) -> Result<(), Error> {
+        if src.p != self.p {
+            return Err(Error::IncompatiblePrecision);
+        }
+        self.hash.check_compatible(src.hash)?;
+        for (j, value) in src.values().enumerate() {
+            self.update(j, value);
+        }
+        Ok(())
+    }
+
+    /// Merge a `HyperLogLog` counter into the current one.
+    ///
+    /// Return an error if the counters don't have the same parameters and
+    /// seed.
+    pub fn merge_hyperloglog(&mut self, src: &HyperLogLog) -> Result<(), Error> {
+        if src.p != self.p {
+            return Err(Error::IncompatiblePrecision);
+        }
+        self.hash.check_compatible(src.hash)?;
+        for (j, &value) in src.registers().iter().enumerate() {
+            self.update(j, value);
+        }
+        Ok(())
+    }
+
+    /// Return a `HyperLogLog` counter with the reconstructed registers.
+    #[must_use]
+    pub fn to_hyperloglog(&self) -> HyperLogLog {
+        HyperLogLog::from_parts(self.p, self.hash, self.values().collect())
+    }
+
+    /// Wipe the counter.
+    pub fn clear(&mut self) {
+        self.registers.fill(0);
+        self.base = 0;
+        self.zeros = 1usize << self.p;
+    }
+
+    // Return the values of the registers, i.e. the base plus their offsets.
+    fn values(&self) -> impl Iterator<Item = u8> + '_ {
+        let m = 1usize << self.p;
+        (0..m).map(move |j| self.base + self.offset(j))
+    }
+
+    #[inline]
+    fn offset(&self, j: usize) -> u8 {
+        (self.registers[j / 2] >> ((j % 2) * 4)) & 0x0f
+    }
+
+    #[inline]
+    fn set_offset(&mut self, j: usize, offset: u8) {
+        let shift = (j % 2) * 4;
+        let byte = &mut self.registers[j / 2];
+        *byte = (*byte & !(0x0f << shift)) | (offset << shift);
+    }
+
+    fn update(&mut self, j: usize, value: u8) -> bool {
+        let offset = self.offset(j);
+        if value <= self.base + offset {
+            return false;
+        }
+        let new_offset = (value - self.base).min(Self::MAX_OFFSET);
+        if new_offset == offset {
+            return false;
+        }
+        self.set_offset(j, new_offset);
+        if offset == 0 {
+            self.zeros -= 1;
+            while self.zeros == 0 {
+                self.rebase();
+            }
+        }
+        true
+    }
+
+    // Raise the base by one, once every register has a non-zero offset.
+    fn rebase(&mut self) {
+        self.base += 1;
+        for j in 0..1usize << self.p {
+            let offset = self.offset(j) - 1;
+            self.set_offset(j, offset);
+            self.zeros += (offset == 0) as usize;
+        }
+    }
+}
+
+#[test]
+fn tailcut_test_registers() {
+    let mut hll = HyperLogLog::new_deterministic(0.2, 1);
+    let mut tailcut = TailCutHyperLogLog::new_from_template(&hll);
+    assert!(tailcut.is_empty());
+    for i in 0..100_000u32 {
+        assert_eq!(tailcut.insert(&i), hll.insert(&i));
+        if i % 997 == 0 {
+            assert_eq!(tailcut.to_hyperloglog(), hll);
+        }
+    }
+    assert!(tailcut.base() > 0);
+    assert_eq!(tailcut.to_hyperloglog(), hll);
+    assert_eq!(tailcut.len(), hll.len());
+    tailcut.clear();
+    assert!(tailcut.is_empty());
+}
+
+#[test]
+fn tailcut_test_merge() {
+    let mut hll = HyperLogLog::new_deterministic(0.001, 1);
+    let mut a = TailCutHyperLogLog::new_from_template(&hll);
+    let mut b = TailCutHyperLogLog::new_from_template(&hll);
+    for i in 0..200_000u32 {
+        hll.insert(&i);
+        if i % 2 == 0 {
+            a.insert(&i);
+        } else {
+            b.insert(&i);
+        }
+    }
+    a.merge(&b).unwrap();
+    let cut = a.to_hyperloglog();
+    let pairs = cut.registers().iter().zip(hll.registers());
+    assert!(pairs.clone().all(|(&x, &y)| x <= y));
+    assert!(pairs.filter(|(&x, &y)| x != y).count() < 16);
+    assert!((a.len() - hll.len()).abs() < hll.len() * 1e-4);
+    assert!(a.size_in_bytes() < hll.registers().len());
+
+    let mut c = TailCutHyperLogLog::new_from_template(&hll);
+    c.merge_hyperloglog(&hll).unwrap();
+    assert_eq!(c, a);
+    let other = TailCutHyperLogLog::new_deterministic(0.001, 2);
+    assert_eq!(a.merge(&other), Err(Error::IncompatibleSeed));
+}
@@ -0,0 +1,209 @@
+//! A `HyperLogLog` counter split into rotating time buckets.
+
+use std::collections::VecDeque;
+use std::hash::Hash;
+
+use crate::HyperLogLog;
+
+/// A set of `HyperLogLog` counters covering consecutive, fixed-duration
+/// time buckets, such as 24 hourly counters.
+///
+/// Values are inserted into the bucket their timestamp falls in. When a
+/// timestamp falls after the newest bucket, buckets rotate: the oldest ones
+/// are dropped and new, empty ones are created. The number of distinct
+/// values over the last `k` buckets is computed by merging them.
+///
+/// Timestamps are arbitrary `u64` units, such as seconds. Bucket `i` covers
+/// the timestamps `t` such that `t / bucket_duration == i`.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowedHyperLogLog {
+    bucket_duration: u64,
+    // Index of the newest bucket, if any timestamp was seen.
+    current: Option<u64>,
+    // Oldest bucket first.
+    buckets: VecDeque<HyperLogLog>,
+}
+
+impl WindowedHyperLogLog {
+    /// Create a new `WindowedHyperLogLog` counter with the given error rate,
+    /// number of buckets, bucket duration and seed.
+    #[must_use]
+    pub fn new_deterministic(
+        error_rate: f64,
+        buckets: usize,
+        bucket_duration: u64,
+        seed: u128,
+    ) -> Self {
+        Self::with_buckets(
+            &HyperLogLog::new_deterministic(error_rate, seed),
+            buckets,
+            bucket_duration,
+        )
+    }
+
+    /// Create a new `WindowedHyperLogLog` counter with the given error rate,
+    /// number of buckets, bucket duration and a random seed. Requires the
+    /// `random` feature.
+    #[cfg(feature = "random")]
+    #[must_use]
+    pub fn new(error_rate: f64, buckets: usize, bucket_duration: u64) -> Self {
+        let seed: u128 = rand::random();
+        Self::new_deterministic(error_rate, buckets, bucket_duration, seed)
+    }
+
+    /// Create a new, empty `WindowedHyperLogLog` counter with the given
+    /// number of buckets and bucket duration, whose buckets have the same
+    /// parameters as `template`.
+    #[must_use]
+    pub fn with_buckets(template: &HyperLogLog, buckets: usize, bucket_duration: u64) -> Self {
+        assert!(buckets > 0 && bucket_duration > 0);
+        WindowedHyperLogLog {
+            bucket_duration,
+            current: None,
+            buckets: (0..buckets)
+                .map(|_| HyperLogLog::new_from_template(template))
+                .collect(),
+        }
+    }
+
+    /// Return the number of buckets.
+    #[must_use]
+    pub fn num_buckets(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Return the duration of a bucket.
+    #[must_use]
+    pub fn bucket_duration(&self) -> u64 {
+        self.bucket_duration
+    }
+
+    /// Insert a new value, seen at `timestamp`.
+    ///
+    /// Values older than the oldest bucket are ignored.
+    pub fn insert<V: Hash>(&mut self, value: &V, timestamp: u64) {
+        if let Some(bucket) = self.bucket_mut(timestamp) {
+            bucket.insert(value);
+        }
+    }
+
+    /// Insert a new u64 value, seen at `timestamp`.
+    ///
+    /// Values older than the oldest bucket are ignored.
+    pub fn insert_by_hash_value(&mut self, x: u64, timestamp: u64) {
+        if let Some(bucket) = self.bucket_mut(timestamp) {
+            bucket.insert_by_hash_value(x);
+        }
+    }
+
+    /// Rotate the buckets so that the newest one covers `now`, without
+    /// inserting anything. Buckets only rotate forward.
+    pub fn advance(&mut self, now: u64) {
+        let index = now / self.bucket_duration;
+        let current = match self.current {
+            Some(current) if current >= index => return,
+            Some(current) => current,
+            None => {
+                self.current = Some(index);
+                return;
+            }
+        };
+        let shift = (index - current).min(self.buckets.len() as u64);
+        for _ in 0..shift {
+            let mut bucket = self.buckets.pop_front().unwrap();
+            bucket.clear();
+            self.buckets.push_back(bucket);
+        }
+        self.current = Some(index);
+    }
+
+    pub(crate) fn current_bucket(&self) -> Option<u64> {
+        self.current
+    }
+
+    /// Return the counters of the buckets, from the oldest to the newest.
+    pub fn buckets(&self) -> impl DoubleEndedIterator<Item = &HyperLogLog> + ExactSizeIterator {
+        self.buckets.iter()
+    }
+
+    /// Return a counter with the values of the `k` newest buckets. `k` is
+    /// capped to the number of buckets.
+    #[must_use]
+    pub fn union_last(&self, k: usize) -> HyperLogLog {
+        let mut hll = HyperLogLog::new_from_template(&self.buckets[0]);
+        let k = k.min(self.buckets.len());
+        let srcs: Vec<_> = self.buckets.iter().rev().take(k).collect();
+        hll.merge_many(&srcs);
+        hll
+    }
+
+    /// Return the number of distinct values in the `k` newest buckets.
+    #[must_use]
+    pub fn len_last(&self, k: usize) -> f64 {
+        self.union_last(k).len()
+    }
+
+    /// Return the number of distinct values in all the buckets.
+    #[must_use]
+    pub fn len(&self) -> f64 {
+        self.len_last(self.buckets.len())
+    }
+
+    /// Return `true` if all the buckets are empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buckets.iter().all(HyperLogLog::is_empty)
+    }
+
+    /// Wipe the counter.
+    pub fn clear(&mut self) {
+        for bucket in &mut self.buckets {
+            bucket.clear();
+        }
+        self.current = None;
+    }
+
+    fn bucket_mut(&mut self, timestamp: u64) -> Option<&mut HyperLogLog> {
+        self.advance(timestamp);
+        let age = self.current? - timestamp / self.bucket_duration;
+        let n = self.buckets.len() as u64;
+        if age >= n {
+            return None;
+        }
+        self.buckets.get_mut((n - 1 - age) as usize)
+    }
+}
+
+#[test]
+fn windowed_test_rotation() {
+    let mut hll = WindowedHyperLogLog::new_deterministic(0.001, 24, 3600, 1);
+    assert!(hll.is_empty());
+    for hour in 0..48u64 {
+        for i in 0..100u64 {
+            hll.insert(&(hour * 100 + i), hour * 3600 + i);
+        }
+    }
+    assert!((hll.len() - 2400.0).abs() < 100.0);
+    assert!((hll.len_last(1) - 100.0).abs() < 5.0);
+    assert!((hll.len_last(6) - 600.0).abs() < 25.0);
+    assert!((hll.len_last(100) - hll.len()).abs() < f64::EPSILON);
+    assert!(hll.len_last(0) == 0.0);
+
+    let mut expected = HyperLogLog::new_deterministic(0.001, 1);
+    expected.insert_all(4700..4800u64);
+    assert_eq!(hll.union_last(1), expected);
+
+    hll.insert(&0u64, 0);
+    hll.insert(&1_000_000u64, 47 * 3600);
+    expected.insert(&1_000_000u64);
+    assert_eq!(hll.union_last(1), expected);
+    assert_eq!(hll.buckets().count(), 24);
+
+    hll.advance(60 * 3600);
+    assert!((hll.len() - 1100.0).abs() < 50.0);
+    hll.advance(1000 * 3600);
+    assert!(hll.is_empty());
+    hll.clear();
+    assert!(hll.is_empty());
+}